@@ -9,15 +9,84 @@ mod macro_types;
 
 use macro_types::*;
 
+/// Commands added to every bot, implemented in `catinator::hooks::admin`
+const BUILTINS: [(&str, &str, &str); 5] = [
+    (
+        "hooks",
+        "List all commands, matchers and hooks and if they are disabled in a channel",
+        "Anyone",
+    ),
+    (
+        "enable",
+        "Enable a command, matcher or hook in a channel",
+        "Op",
+    ),
+    (
+        "disable",
+        "Disable a command, matcher or hook in a channel",
+        "Op",
+    ),
+    (
+        "ignore",
+        "Ignore a nick, hostmask or $a:account, or list everything that is ignored",
+        "Admin",
+    ),
+    (
+        "unignore",
+        "Stop ignoring a nick, hostmask or $a:account",
+        "Admin",
+    ),
+];
+
+fn generate_builtins() -> proc_macro2::TokenStream {
+    let builtins = BUILTINS.iter().map(|(name, _, level)| {
+        let function = Ident::new(name, Span::call_site());
+        let level = Ident::new(level, Span::call_site());
+
+        quote! {
+            if #name == rest
+                && bot.check_permission(&message, #name, catinator::permission::Level::#level)
+            {
+                debug!(target: "command", "{} with {:?}", #name, message);
+                let ctx = catinator::Context::new(&bot, &message).with_input(input);
+                let result = bot.isolate(Some(&message), catinator::Kind::Command, #name, || {
+                    catinator::hooks::admin::#function(&bot, ctx)
+                });
+                let result = bot.handled(catinator::Kind::Command, #name, result);
+
+                if let Err(err) = result {
+                    bot.handle_error(Some(&message), catinator::Kind::Command, #name, err)
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#builtins)*
+    }
+}
+
 fn generate_help(items: &Items) -> proc_macro2::TokenStream {
     let command_help = items.inner.iter().filter_map(|x| {
-        if let Item::Command(command) = x {
-            let help = command.help();
-            Some(quote! {
-                bot.send_notice(target, #help).unwrap();
-            })
-        } else {
-            None
+        let help = match x {
+            Item::Command(command) => vec![command.help()],
+            Item::Group(group) => std::iter::once(group.help())
+                .chain(group.commands.iter().map(|command| command.help()))
+                .collect(),
+            _ => return None,
+        };
+        Some(quote! {
+            #(bot.send_notice(target, #help)?;)*
+        })
+    });
+
+    let builtin_help = BUILTINS.iter().map(|(name, description, level)| {
+        let help = match *level {
+            "Anyone" => format!("  {}: {}", name, description),
+            level => format!("  {} ({}): {}", name, level.to_lowercase(), description),
+        };
+        quote! {
+            bot.send_notice(target, #help)?;
         }
     });
 
@@ -25,7 +94,7 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
         if let Item::Matcher(matcher) = x {
             let help = matcher.help();
             Some(quote! {
-                bot.send_notice(target, #help).unwrap();
+                bot.send_notice(target, #help)?;
             })
         } else {
             None
@@ -36,45 +105,99 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
         if let Item::Hook(hook) = x {
             let help = hook.help();
             Some(quote! {
-                bot.send_notice(target, #help).unwrap();
+                bot.send_notice(target, #help)?;
             })
         } else {
             None
         }
     });
 
-    let gen = quote! {
-        let target = message.source_nickname().unwrap();
-
-        bot.send_notice(target, "COMMANDS:").unwrap();
-        #(#command_help)*
-
-        bot.send_notice(target, "MATCHERS:").unwrap();
-        #(#matcher_help)*
+    let task_help = optional_help(
+        "TASKS:",
+        items.inner.iter().filter_map(|x| match x {
+            Item::Task(task) => Some(task.help()),
+            _ => None,
+        }),
+    );
+
+    let subscriber_help = optional_help(
+        "SUBSCRIBERS:",
+        items.inner.iter().filter_map(|x| match x {
+            Item::Subscriber(subscriber) => Some(subscriber.help()),
+            _ => None,
+        }),
+    );
+
+    notify_sender(
+        &"help",
+        quote! {
+            bot.send_notice(target, "COMMANDS:")?;
+            #(#command_help)*
+            #(#builtin_help)*
+
+            bot.send_notice(target, "MATCHERS:")?;
+            #(#matcher_help)*
+
+            bot.send_notice(target, "HOOKS:")?;
+            #(#hook_help)*
+
+            #task_help
+            #subscriber_help
+        },
+    )
+}
 
-        bot.send_notice(target, "HOOKS:").unwrap();
-        #(#hook_help)*
-    };
-    gen.into()
+/// Help for a section that is left out if it is empty
+fn optional_help(title: &str, help: impl Iterator<Item = String>) -> proc_macro2::TokenStream {
+    let help: Vec<String> = help.collect();
+    if help.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            bot.send_notice(target, #title)?;
+            #(bot.send_notice(target, #help)?;)*
+        }
+    }
 }
 
 /// Main entrypoint to the bot
 ///
 /// ```no_run
 /// # extern crate tokio;
-/// # use macros::catinator;
+/// # use catinator_macros::catinator;
 /// # use anyhow::Result;
 /// #
 /// # fn function(bot: &catinator::Bot, msg: irc::client::prelude::Message) -> Result<()> {
 /// #   Ok(())
 /// # }
 /// #
+/// # fn task(bot: &catinator::Bot) -> Result<()> {
+/// #   Ok(())
+/// # }
+/// #
+/// # fn subscriber(bot: &catinator::Bot, event: &String) -> Result<()> {
+/// #   Ok(())
+/// # }
+/// #
 /// #[tokio::main]
 /// async fn main() {
+///   let mut bot = catinator::Bot::new().await.unwrap();
+///
 ///   catinator!(
-///     hook("name", "A short description", PRIVMSG, function)
-///     command("name", "A short description", function)
-///     matcher("name", "A short description", r"^\[.*?\]$", function)
+///     hook("log", "A short description", PRIVMSG, function)
+///     hook("welcome", "A short description", RPL_WELCOME, function)
+///     hook("presence", "A short description", [JOIN, PART, QUIT], function)
+///     command("command", "A short description", function)
+///     command("admin", "A command only admins can use", function, permission = admin)
+///     matcher("matcher", "A short description", r"^\[.*?\]$", function)
+///     matcher("first", "A short description", r"^\S{3}$", function, priority = 1, consume = true)
+///     group("group", "A short description", [
+///       command("sub", "A short description", function)
+///       command("other", "A short description", function, cooldown = "5s")
+///     ])
+///     interval("poll", "A short description", "30m", task, missed = delay)
+///     cron("weekly", "A short description", "0 9 * * 1", task)
+///     subscribe("titles", "A short description", String, subscriber)
 ///   );
 /// }
 /// ```
@@ -84,13 +207,29 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
 /// The first two arguments are the name and description respectively,
 /// the last argument is the function that gets executed.
 ///
-/// The function must of of the following type:
+/// Names are shared by all commands, matchers and hooks and have to be unique,
+/// they must not contain whitespace. Matcher regexes are checked when compiling.
+///
+/// The function takes the bot and a `catinator::Context` with information about the
+/// message, like the senders nick, the command arguments or the matchers regex captures,
+/// and helpers like `ctx.reply()`:
+/// ```
+/// fn hook(bot: &catinator::Bot, ctx: catinator::Context) -> anyhow::Result<()> {
+///    ctx.reply("meow")
+/// }
+/// ```
+///
+/// Functions can also take the raw message instead:
 /// ```
 /// fn hook(bot: &catinator::Bot, msg: irc::client::prelude::Message) -> anyhow::Result<()> {
 ///    Ok(())
 /// }
 /// ```
 ///
+/// Errors returned by functions are reported to the user that triggered them,
+/// return a `catinator::error::UserError` for errors meant to be shown to the user.
+/// See `catinator::error` for details.
+///
 /// ## async
 /// You can run async functions natively by prepending your function
 /// hooks etc. with the async keyword.
@@ -107,9 +246,17 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
 /// hook("name", "description", COMMAND, function)
 /// ```
 ///
-/// PRIVMSG is an IRC Command like PRIVMSG or AUTHENTICATE
+/// COMMAND is an IRC Command like PRIVMSG or AUTHENTICATE
 /// Any of the enum variants of [the irc crate](https://docs.rs/irc/0.15.0/irc/client/prelude/enum.Command.html)
-/// should work.
+/// should work. `Response` runs on every numeric reply, specific numerics can be
+/// used by their name like `RPL_WELCOME` or `ERR_NICKNAMEINUSE`, see
+/// [Response](https://docs.rs/irc/latest/irc/client/prelude/enum.Response.html).
+///
+/// To run on several kinds at once use a list:
+///
+/// ```ignore
+/// hook("name", "description", [JOIN, PART, QUIT], function)
+/// ```
 ///
 /// ## command
 /// A Command is command that can be executed in any PRIVMSG and is
@@ -119,6 +266,122 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
 /// command("name", "description", function)
 /// ```
 /// Would be ":name <whatever>" in an irc channel or private message.
+/// Commands can also be invoked by addressing the bot, like "catinator: name <whatever>",
+/// or without a prefix in a private message, see `catinator::dispatch`.
+///
+/// ## group
+/// A group bundles commands as sub-commands under a common name.
+///
+/// ```ignore
+/// group("quote", "Manage quotes", [
+///     command("add", "Add a quote", quote::add, permission = voiced)
+///     async command("rand", "Show a random quote", quote::rand)
+/// ])
+/// ```
+/// Would be ":quote add <whatever>" and ":quote rand", invoking the group without
+/// a known sub-command lists its sub-commands.
+/// Sub-commands are named like "quote add" for permissions, cooldowns and `:disable`,
+/// disabling the group disables all of its sub-commands.
+///
+/// ## interval and cron
+/// Tasks run a function periodically, alongside handling messages.
+///
+/// ```ignore
+/// interval("name", "description", "30m", function)
+/// async cron("name", "description", "0 9 * * 1", function)
+/// ```
+///
+/// Intervals take a duration like `"30s"` or `"1h30m"`, cron expressions the five
+/// fields `minute hour day month weekday`, see `catinator::schedule`. Both are
+/// checked when compiling. Task functions only take the bot:
+///
+/// ```
+/// fn task(bot: &catinator::Bot) -> anyhow::Result<()> {
+///    Ok(())
+/// }
+/// ```
+///
+/// If the bot was busy when a task was due, the `missed` option decides what happens:
+///
+/// - `missed = skip`: run once and continue on schedule (default)
+/// - `missed = burst`: run once for every missed tick
+/// - `missed = delay`: run once and start the next interval from now, intervals only
+///
+/// ## subscribe
+/// Subscribers receive events of a type emitted with `bot.emit(event)`,
+/// see `catinator::events`.
+///
+/// ```ignore
+/// subscribe("name", "description", my_crate::TitleResolved, function)
+/// ```
+///
+/// The function takes the bot and a reference to the event:
+///
+/// ```
+/// struct TitleResolved;
+///
+/// fn subscriber(bot: &catinator::Bot, event: &TitleResolved) -> anyhow::Result<()> {
+///    Ok(())
+/// }
+/// ```
+///
+/// ## builtin commands
+/// Besides `help` the following commands are always available:
+///
+/// ```text
+/// :hooks [#channel]                list everything and if it is disabled in a channel
+/// :disable <name> [#channel]       disable a command, matcher or hook in a channel
+/// :enable <name> [#channel]        enable it again
+/// :ignore [nick|mask|$a:account]   ignore a sender, or list everything that is ignored
+/// :unignore <nick|mask|$a:account> stop ignoring a sender
+/// ```
+///
+/// `enable` and `disable` can only be used by channel operators and above,
+/// `ignore` and `unignore` by admins. Given a `#channel` the user has to be an
+/// operator in that channel, or an admin, to manage or list it.
+/// The state is persisted to the configured `state_file`.
+///
+/// Messages of ignored senders do not trigger commands, matchers or hooks.
+/// Hooks that should still run for them, like logging, can opt in:
+///
+/// ```ignore
+/// hook("log", "description", PRIVMSG, function, ignored = true)
+/// ```
+///
+/// ## panics
+/// A panicking item does not take down the bot, the panic is caught and logged
+/// and reported like any other error. With the `panic_limit` setting items that
+/// panic repeatedly are disabled until they are re-enabled by an admin
+/// with `enable <name>`.
+/// See `catinator::isolation`.
+///
+/// ## middleware
+/// Middleware added with `bot.add_middleware` before the macro runs around dispatch:
+/// it can drop incoming messages before any item sees them, modify or drop
+/// outgoing messages and inspect or replace the result of every item.
+/// See `catinator::middleware`.
+///
+/// # Options
+/// Items can take additional `key = value` options after the function.
+///
+/// ## permission
+/// The permission level (`catinator::permission::Level`) required to use a command,
+/// one of `anyone` (default), `identified`, `voiced`, `op`, `admin` or `owner`.
+/// Users without the required level get a notice and the attempt is logged.
+///
+/// ```ignore
+/// command("join", "join a channel", function, permission = admin)
+/// ```
+///
+/// ## cooldown and channel_cooldown
+/// Time a user has to wait between uses of a command, and the time that has
+/// to pass between uses in the same channel. Durations are written like `"30s"`
+/// or `"1h30m"` and can be overridden with the `cooldowns` setting.
+/// Admins are not affected by cooldowns.
+///
+/// ```ignore
+/// command("wa", "ask wolfram alpha", function, cooldown = "30s", channel_cooldown = "5s")
+/// ```
 ///
 /// ## matcher
 /// A matcher matches on a PRIVMSG using regex.
@@ -128,51 +391,108 @@ fn generate_help(items: &Items) -> proc_macro2::TokenStream {
 /// ```
 ///
 /// The [regex crate](https://docs.rs/regex) is used for matching, see it's documentation for details.
+/// The captures of every match in the message are passed to the function in
+/// `ctx.captures`, `ctx.capture(1)` gets the first group of the first match and
+/// `ctx.capture_all(1)` the first group of all matches.
+///
+/// Every matcher whose regex matches runs, in declaration order,
+/// on messages that do not start with the command prefix.
+/// This can be changed with the following options:
+///
+/// ```ignore
+/// matcher("nitter", "description", r"regex", function, priority = 10, consume = true, prefixed = true)
+/// ```
+///
+/// - `priority`: matchers with a higher priority run first (default: 0)
+/// - `consume`: once this matcher handled a message successfully no further matchers run on it (default: false)
+/// - `prefixed`: also run on messages starting with the command prefix (default: false)
 ///
 #[proc_macro]
 pub fn catinator(tokens: TokenStream) -> TokenStream {
     let items = parse_macro_input!(tokens as Items);
 
-    let hooks = items.inner.iter().filter_map(|x| {
-        if let Item::Hook(hook) = x {
-            Some(hook.to_call())
+    let tasks: Vec<&Task> = items
+        .inner
+        .iter()
+        .filter_map(|x| {
+            if let Item::Task(task) = x {
+                Some(task)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let task_registrations = tasks.iter().map(|task| task.register());
+
+    let subscribers = items.inner.iter().filter_map(|x| {
+        if let Item::Subscriber(subscriber) = x {
+            Some(subscriber.to_call())
         } else {
             None
         }
     });
+    let task_calls = tasks.iter().map(|task| task.to_call());
 
-    let commands = items.inner.iter().filter_map(|x| {
-        if let Item::Command(command) = x {
-            Some(command.to_call())
+    let hooks = items.inner.iter().filter_map(|x| {
+        if let Item::Hook(hook) = x {
+            Some(hook.to_call())
         } else {
             None
         }
     });
 
-    let matchers = items.inner.iter().filter_map(|x| {
-        if let Item::Matcher(command) = x {
-            Some(command.to_call())
-        } else {
-            None
-        }
+    let commands = items.inner.iter().filter_map(|x| match x {
+        Item::Command(command) => Some(command.to_call()),
+        Item::Group(group) => Some(group.to_call()),
+        _ => None,
     });
 
+    let mut matchers: Vec<&Matcher> = items
+        .inner
+        .iter()
+        .filter_map(|x| {
+            if let Item::Matcher(matcher) = x {
+                Some(matcher)
+            } else {
+                None
+            }
+        })
+        .collect();
+    // stable sort, matchers with the same priority keep their declaration order
+    matchers.sort_by_key(|matcher| std::cmp::Reverse(matcher.priority));
+
+    let consumed = if matchers.iter().any(|matcher| matcher.consume) {
+        quote! { let mut consumed = false; }
+    } else {
+        quote! { let consumed = false; }
+    };
+
+    let matchers = matchers.iter().map(|matcher| matcher.to_call());
+
     let matchers_regex = items.inner.iter().filter_map(|x| {
         if let Item::Matcher(matcher) = x {
-            let name = &matcher.name;
             let regex = &matcher.matcher;
-
-            let ident = Ident::new(&name.value(), Span::call_site());
+            let ident = matcher.regex_ident();
 
             Some(quote! {
-                let #ident = regex::Regex::new(#regex).unwrap();
+                let #ident = regex::Regex::new(#regex).expect("regex is validated by the catinator macro");
             })
         } else {
             None
         }
     });
 
+    let handlers = items.inner.iter().map(|x| match x {
+        Item::Command(command) => command.handler(),
+        Item::Group(group) => group.handler(),
+        Item::Hook(hook) => hook.handler(),
+        Item::Matcher(matcher) => matcher.handler(),
+        Item::Task(task) => task.handler(),
+        Item::Subscriber(subscriber) => subscriber.handler(),
+    });
+
     let help = generate_help(&items);
+    let builtins = generate_builtins();
 
     let gen = quote! {
         use std::env;
@@ -185,41 +505,79 @@ pub fn catinator(tokens: TokenStream) -> TokenStream {
 
         #(#matchers_regex)*
 
+        bot.handlers = vec![#(#handlers),*];
+
         info!("starting main event loop");
-        let mut stream = bot.irc_client.stream().unwrap();
+        let mut stream = bot.stream().unwrap();
+
+        let mut __scheduler = catinator::schedule::Scheduler::new();
+        let mut __events = bot.events.take_receiver().expect("the event loop is only started once");
+        let mut __reloader = catinator::reload::Reloader::new(
+            catinator::config::Config::files(),
+            bot.config.settings.reload_interval,
+        );
+        #(#task_registrations)*
+
+        loop {
+            let message = tokio::select! {
+                message = stream.next() => match message.transpose().unwrap() {
+                    Some(message) => message,
+                    None => break,
+                },
+                __task = __scheduler.next() => {
+                    #(#task_calls)*
+                    continue;
+                }
+                Some(__event) = __events.recv() => {
+                    #(#subscribers)*
+                    continue;
+                }
+                _ = __reloader.next() => {
+                    if let Err(err) = bot.reload() {
+                        tracing::error!("failed to reload config: {:?}", err);
+                    }
+                    continue;
+                }
+            };
 
-        while let Some(message) = stream.next().await.transpose().unwrap() {
             trace!("{:?}", message);
 
             let command = message.clone().command;
 
+            bot.observe(&message);
+            if !bot.incoming(&message) {
+                continue;
+            }
+            let ignored = bot.is_ignored(&message);
+
             #(#hooks)*
 
             match &command {
-                Command::PRIVMSG(_target, text) => {
-                    let mut word = match text.split_ascii_whitespace().next() {
-                        Some(word) => word.chars(),
-                        None => continue,
-                    };
-                    let prefix = word.next().unwrap();
-                    let rest: String = word.collect();
-
-                    if prefix == bot.config.settings.prefix {
+                Command::PRIVMSG(target, text) if !ignored => {
+                    let invocation = bot.parse_command(target, text);
+                    let prefixed = invocation.is_some_and(|invocation| invocation.addressed);
+
+                    if let Some(invocation) = invocation {
+                        let rest = invocation.name;
+                        let input = invocation.input;
+
                         if "help" == rest {
                             #help
                         }
 
                         #(#commands)*
-                    } else {
-                        #(#matchers)*
+                        #builtins
                     }
+
+                    #consumed
+                    #(#matchers)*
                 }
                 _ => (),
             }
         }
     };
 
-    return gen.into();
+    gen.into()
 }
 
 /// Match on a privmsg and execute the function block on it
@@ -228,7 +586,7 @@ pub fn catinator(tokens: TokenStream) -> TokenStream {
 /// ```
 /// # use anyhow::Result;
 /// # use irc::client::prelude::*;
-/// # use macros::privmsg;
+/// # use catinator_macros::privmsg;
 /// #
 /// # pub fn hook(bot: &catinator::Bot, msg: Message) -> Result<()> {
 /// privmsg!(msg, {
@@ -257,5 +615,5 @@ pub fn privmsg(tokens: TokenStream) -> TokenStream {
             _ => Ok(()),
         }
     };
-    return gen.into();
+    gen.into()
 }
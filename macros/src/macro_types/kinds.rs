@@ -0,0 +1,165 @@
+use proc_macro2::Ident;
+use quote::quote;
+
+use syn::{
+    bracketed,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Token,
+};
+
+/// Variants of `irc::proto::Command` a hook can be registered for
+const COMMANDS: &[&str] = &[
+    "PASS",
+    "NICK",
+    "USER",
+    "OPER",
+    "UserMODE",
+    "SERVICE",
+    "QUIT",
+    "SQUIT",
+    "JOIN",
+    "PART",
+    "ChannelMODE",
+    "TOPIC",
+    "NAMES",
+    "LIST",
+    "INVITE",
+    "KICK",
+    "PRIVMSG",
+    "NOTICE",
+    "MOTD",
+    "LUSERS",
+    "VERSION",
+    "STATS",
+    "LINKS",
+    "TIME",
+    "CONNECT",
+    "TRACE",
+    "ADMIN",
+    "INFO",
+    "SERVLIST",
+    "SQUERY",
+    "WHO",
+    "WHOIS",
+    "WHOWAS",
+    "KILL",
+    "PING",
+    "PONG",
+    "ERROR",
+    "AWAY",
+    "REHASH",
+    "DIE",
+    "RESTART",
+    "SUMMON",
+    "USERS",
+    "WALLOPS",
+    "USERHOST",
+    "ISON",
+    "SAJOIN",
+    "SAMODE",
+    "SANICK",
+    "SAPART",
+    "SAQUIT",
+    "NICKSERV",
+    "CHANSERV",
+    "OPERSERV",
+    "BOTSERV",
+    "HOSTSERV",
+    "MEMOSERV",
+    "CAP",
+    "AUTHENTICATE",
+    "ACCOUNT",
+    "METADATA",
+    "MONITOR",
+    "BATCH",
+    "CHGHOST",
+    "Response",
+    "Raw",
+];
+
+/// What a hook runs on, either a `Command` variant like `JOIN`,
+/// or a specific numeric reply like `RPL_WELCOME`
+pub enum HookKind {
+    Command(Ident),
+    Response(Ident),
+}
+
+impl HookKind {
+    pub fn ident(&self) -> &Ident {
+        match self {
+            HookKind::Command(ident) | HookKind::Response(ident) => ident,
+        }
+    }
+
+    /// The pattern matching a `Command` of this kind
+    pub fn pattern(&self) -> proc_macro2::TokenStream {
+        match self {
+            HookKind::Command(ident) => quote! { Command::#ident(..) },
+            HookKind::Response(ident) => quote! { Command::Response(Response::#ident, ..) },
+        }
+    }
+}
+
+impl Parse for HookKind {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+        let name = ident.to_string();
+
+        if name.starts_with("RPL_") || name.starts_with("ERR_") {
+            // unknown numerics are reported by the compiler on the generated `Response::#ident`
+            return Ok(HookKind::Response(ident));
+        }
+
+        if COMMANDS.contains(&name.as_str()) {
+            return Ok(HookKind::Command(ident));
+        }
+
+        let suggestion = COMMANDS
+            .iter()
+            .find(|command| command.eq_ignore_ascii_case(&name))
+            .map(|command| format!(", did you mean {}?", command))
+            .unwrap_or_default();
+
+        Err(syn::Error::new(
+            ident.span(),
+            format!(
+                "unknown hook kind {}{}\n\
+                 expected a variant of irc::proto::Command like JOIN or PRIVMSG, \
+                 or a numeric reply like RPL_WELCOME or ERR_NICKNAMEINUSE",
+                name, suggestion
+            ),
+        ))
+    }
+}
+
+/// Parse a single [HookKind] or a list of them like `[JOIN, PART]`
+pub fn parse_kinds(input: ParseStream) -> syn::Result<Vec<HookKind>> {
+    if !input.peek(syn::token::Bracket) {
+        return Ok(vec![input.parse()?]);
+    }
+
+    let content;
+    let brackets = bracketed!(content in input);
+    let kinds: Vec<HookKind> = Punctuated::<HookKind, Token![,]>::parse_terminated(&content)?
+        .into_iter()
+        .collect();
+
+    if kinds.is_empty() {
+        return Err(syn::Error::new(
+            brackets.span.join(),
+            "expected at least one hook kind",
+        ));
+    }
+
+    for (i, kind) in kinds.iter().enumerate() {
+        if kinds[..i].iter().any(|other| other.ident() == kind.ident()) {
+            return Err(syn::Error::new(
+                kind.ident().span(),
+                format!("duplicate hook kind {}", kind.ident()),
+            ));
+        }
+    }
+
+    Ok(kinds)
+}
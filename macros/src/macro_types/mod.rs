@@ -1,18 +1,25 @@
-use proc_macro2::{Ident, Span};
-use quote::{quote, ToTokens};
+use proc_macro2::Ident;
+use quote::{format_ident, quote, ToTokens};
 
 use syn::{
-    parenthesized,
+    bracketed, parenthesized,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
-    LitStr, Path, Token,
+    Lit, LitStr, Path, Token,
 };
 
+pub mod kinds;
+pub mod options;
 pub mod privmsg;
 
+use kinds::{parse_kinds, HookKind};
+use options::{parse_options, validate_cron, DurationOption, OptionValue, Permission};
+
 pub trait IrcItem {
     fn to_call(&self) -> proc_macro2::TokenStream;
     fn help(&self) -> String;
+    /// The `catinator::Handler` describing this item at runtime
+    fn handler(&self) -> proc_macro2::TokenStream;
 }
 
 pub struct Items {
@@ -32,14 +39,84 @@ impl Parse for Items {
             }
         }
 
+        validate_names(&items)?;
+
+        items
+            .iter_mut()
+            .filter_map(|item| match item {
+                Item::Matcher(matcher) => Some(matcher),
+                _ => None,
+            })
+            .enumerate()
+            .for_each(|(index, matcher)| matcher.index = index);
+
         Ok(Self { inner: items })
     }
 }
 
+/// Check that names are valid and unique,
+/// they are shared by everything for disabling them, permissions and cooldowns.
+fn validate_names(items: &[Item]) -> syn::Result<()> {
+    let mut seen: Vec<String> = Vec::new();
+
+    for item in items {
+        let names: Vec<(String, &LitStr)> = match item {
+            Item::Command(command) => vec![(command.full_name(), &command.name)],
+            Item::Group(group) => std::iter::once((group.name.value(), &group.name))
+                .chain(
+                    group
+                        .commands
+                        .iter()
+                        .map(|command| (command.full_name(), &command.name)),
+                )
+                .collect(),
+            Item::Hook(hook) => vec![(hook.name.value(), &hook.name)],
+            Item::Matcher(matcher) => vec![(matcher.name.value(), &matcher.name)],
+            Item::Task(task) => vec![(task.name.value(), &task.name)],
+            Item::Subscriber(subscriber) => vec![(subscriber.name.value(), &subscriber.name)],
+        };
+
+        for (name, lit) in names {
+            let value = lit.value();
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    "names must not be empty or contain whitespace",
+                ));
+            }
+
+            let builtin = matches!(item, Item::Command(_) | Item::Group(_))
+                && (name == "help"
+                    || crate::BUILTINS
+                        .iter()
+                        .any(|(builtin, _, _)| *builtin == name));
+            if builtin {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!("{} is the name of a builtin command", name),
+                ));
+            }
+
+            if seen.contains(&name) {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!("duplicate name {}", name),
+                ));
+            }
+            seen.push(name);
+        }
+    }
+
+    Ok(())
+}
+
 pub enum Item {
     Command(Command),
+    Group(Group),
     Hook(Hook),
     Matcher(Matcher),
+    Task(Task),
+    Subscriber(Subscriber),
 }
 
 impl Parse for Item {
@@ -58,6 +135,11 @@ impl Parse for Item {
                     i.asyn = asyn;
                     Item::Command(i)
                 }),
+                "group" if asyn => Err(syn::Error::new(
+                    item.span(),
+                    "groups can not be async, mark their commands as async instead",
+                )),
+                "group" => input.parse().map(Item::Group),
                 "hook" => input.parse().map(|mut i: Hook| {
                     i.asyn = asyn;
                     Item::Hook(i)
@@ -66,9 +148,17 @@ impl Parse for Item {
                     i.asyn = asyn;
                     Item::Matcher(i)
                 }),
+                "interval" | "cron" => Task::parse(input, item == "cron").map(|mut i| {
+                    i.asyn = asyn;
+                    Item::Task(i)
+                }),
+                "subscribe" => input.parse().map(|mut i: Subscriber| {
+                    i.asyn = asyn;
+                    Item::Subscriber(i)
+                }),
                 _ => Err(input.error(format!(
-                    "expected one of: command, hook or matcher not {}",
-                    item.to_string()
+                    "expected one of: command, group, hook, matcher, interval, cron or subscribe not {}",
+                    item
                 ))),
             }
         } else {
@@ -79,40 +169,85 @@ impl Parse for Item {
 
 pub struct Command {
     pub asyn: bool,
+    /// Name of the group this is a sub-command of
+    pub group: Option<String>,
     pub name: LitStr,
     pub description: LitStr,
     pub function: Function,
+    pub permission: Permission,
+    pub cooldown: Option<DurationOption>,
+    pub channel_cooldown: Option<DurationOption>,
+}
+
+impl Command {
+    /// The name including the group, like `quote add`
+    pub fn full_name(&self) -> String {
+        match &self.group {
+            Some(group) => format!("{} {}", group, self.name.value()),
+            None => self.name.value(),
+        }
+    }
 }
 
 impl IrcItem for Command {
     fn to_call(&self) -> proc_macro2::TokenStream {
         let name = &self.name;
+        let full_name = self.full_name();
         let function = &self.function;
+        let permission = self.permission.to_tokens();
+        let cooldown = duration_tokens(&self.cooldown);
+        let channel_cooldown = duration_tokens(&self.channel_cooldown);
 
-        let call = if self.asyn {
-            quote! {
-                #function(&bot, message.clone()).await
-            }
-        } else {
-            quote! {
-                #function(&bot, message.clone())
-            }
-        };
+        let call = isolate(
+            self.asyn,
+            quote! { Some(&message) },
+            "Command",
+            &full_name,
+            quote! { #function(&bot, catinator::FromContext::from_context(ctx)) },
+        );
 
         quote! {
-            if #name == rest {
-                debug!(target: "command", "{} with {:?}", #name, message);
+            if #name == rest
+                && bot.is_enabled(&message, #full_name)
+                && bot.check_permission(&message, #full_name, #permission)
+                && bot.check_cooldown(&message, #full_name, #cooldown, #channel_cooldown)
+            {
+                debug!(target: "command", "{} with {:?}", #full_name, message);
+                let ctx = catinator::Context::new(&bot, &message).with_input(input);
                 let result = #call;
+                let result = bot.handled(catinator::Kind::Command, #full_name, result);
 
                 if let Err(err) = result {
-                    tracing::warn!("error in matcher: {:?}: {:?}", #name, err)
+                    bot.handle_error(Some(&message), catinator::Kind::Command, #full_name, err)
                 }
             }
         }
     }
 
     fn help(&self) -> String {
-        format!("  {}: {}", self.name.value(), self.description.value())
+        if self.permission.0 == "anyone" {
+            format!("  {}: {}", self.full_name(), self.description.value())
+        } else {
+            format!(
+                "  {} ({}): {}",
+                self.full_name(),
+                self.permission.0,
+                self.description.value()
+            )
+        }
+    }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = self.full_name();
+        let description = &self.description;
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Command,
+                name: #name,
+                description: #description,
+            }
+        }
     }
 }
 
@@ -133,11 +268,149 @@ impl Parse for Command {
         _token = content.parse()?;
         let function = content.parse()?;
 
+        let mut permission = Permission::anyone();
+        let mut cooldown = None;
+        let mut channel_cooldown = None;
+        for option in parse_options(&content, &["permission", "cooldown", "channel_cooldown"])? {
+            match option.key.to_string().as_str() {
+                "permission" => permission = Permission::from_option(&option)?,
+                "cooldown" => cooldown = Some(DurationOption::from_option(&option)?),
+                _ => channel_cooldown = Some(DurationOption::from_option(&option)?),
+            }
+        }
+
         Ok(Self {
             asyn: false,
+            group: None,
             name,
             description,
             function,
+            permission,
+            cooldown,
+            channel_cooldown,
+        })
+    }
+}
+
+/// A command with sub-commands, like `:quote add` and `:quote rand`
+pub struct Group {
+    pub name: LitStr,
+    pub description: LitStr,
+    pub commands: Vec<Command>,
+}
+
+impl Group {
+    /// Lines sent to the user when invoking the group without a known sub-command
+    fn usage(&self) -> Vec<String> {
+        let names: Vec<String> = self
+            .commands
+            .iter()
+            .map(|command| command.name.value())
+            .collect();
+
+        std::iter::once(format!(
+            "usage: {} <{}>",
+            self.name.value(),
+            names.join("|")
+        ))
+        .chain(self.commands.iter().map(|command| command.help()))
+        .collect()
+    }
+}
+
+impl IrcItem for Group {
+    fn to_call(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let names = self.commands.iter().map(|command| &command.name);
+        let commands = self.commands.iter().map(|command| command.to_call());
+        let usage = self.usage();
+        let usage = notify_sender(name, quote! { #(bot.send_notice(target, #usage)?;)* });
+
+        quote! {
+            if #name == rest && bot.is_enabled(&message, #name) {
+                match invocation.subcommand() {
+                    Some(invocation) if [#(#names),*].contains(&invocation.name) => {
+                        let rest = invocation.name;
+                        let input = invocation.input;
+
+                        #(#commands)*
+                    }
+                    _ => {
+                        debug!(target: "command", "usage of {} with {:?}", #name, message);
+                        #usage
+                    }
+                }
+            }
+        }
+    }
+
+    fn help(&self) -> String {
+        format!("  {}: {}", self.name.value(), self.description.value())
+    }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let description = &self.description;
+        let commands = self.commands.iter().map(|command| command.handler());
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Command,
+                name: #name,
+                description: #description,
+            },
+            #(#commands),*
+        }
+    }
+}
+
+impl Parse for Group {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        parenthesized!(content in input);
+
+        let mut _token: Token![,];
+
+        if input.peek(Token![,]) {
+            _token = input.parse()?;
+        }
+
+        let name: LitStr = content.parse()?;
+        _token = content.parse()?;
+        let description = content.parse()?;
+        _token = content.parse()?;
+
+        let items;
+        bracketed!(items in content);
+
+        let mut commands = Vec::new();
+        while !items.is_empty() {
+            let span = items.span();
+            match items.parse()? {
+                Item::Command(mut command) => {
+                    command.group = Some(name.value());
+                    commands.push(command);
+                }
+                _ => return Err(syn::Error::new(span, "groups can only contain commands")),
+            }
+        }
+
+        if commands.is_empty() {
+            return Err(syn::Error::new(
+                name.span(),
+                "groups need at least one command",
+            ));
+        }
+
+        if content.peek(Token![,]) {
+            _token = content.parse()?;
+        }
+        parse_options(&content, &[])?;
+
+        Ok(Self {
+            name,
+            description,
+            commands,
         })
     }
 }
@@ -146,34 +419,47 @@ pub struct Hook {
     pub asyn: bool,
     pub name: LitStr,
     pub description: LitStr,
-    pub kind: Ident,
+    /// The hook runs if a message is of any of these kinds
+    pub kinds: Vec<HookKind>,
     pub function: Function,
+    /// Also run for senders on the ignore list
+    pub ignored: bool,
 }
 
 impl IrcItem for Hook {
     fn to_call(&self) -> proc_macro2::TokenStream {
         let name = &self.name;
-        let kind = &self.kind;
-        let kind_str = &self.kind.to_string();
-        let function = &self.function;
-
-        let call = if self.asyn {
-            quote! {
-                #function(&bot, message.clone()).await
-            }
+        let kinds = self.kinds.iter().map(|kind| kind.pattern());
+        let ignored = if self.ignored {
+            quote! {}
         } else {
-            quote! {
-                #function(&bot, message.clone())
-            }
+            quote! { !ignored && }
         };
+        let kind_str = self
+            .kinds
+            .iter()
+            .map(|kind| kind.ident().to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let function = &self.function;
+
+        let call = isolate(
+            self.asyn,
+            quote! { Some(&message) },
+            "Hook",
+            &name,
+            quote! { #function(&bot, catinator::FromContext::from_context(ctx)) },
+        );
 
         quote! {
-            if let Command::#kind(..) = &command {
+            if let (#(#kinds)|*, true) = (&command, #ignored bot.is_enabled(&message, #name)) {
                 debug!(target: "hook", "{} of kind {} with {:?}", #name, #kind_str, message);
+                let ctx = catinator::Context::new(&bot, &message);
                 let result = #call;
+                let result = bot.handled(catinator::Kind::Hook, #name, result);
 
                 if let Err(err) = result {
-                    tracing::warn!("error in matcher: {:?}: {:?}", #name, err)
+                    bot.handle_error(Some(&message), catinator::Kind::Hook, #name, err)
                 }
             }
         }
@@ -182,6 +468,19 @@ impl IrcItem for Hook {
     fn help(&self) -> String {
         format!("  {}: {}", self.name.value(), self.description.value())
     }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let description = &self.description;
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Hook,
+                name: #name,
+                description: #description,
+            }
+        }
+    }
 }
 
 impl Parse for Hook {
@@ -199,52 +498,58 @@ impl Parse for Hook {
         _token = content.parse()?;
         let description = content.parse()?;
         _token = content.parse()?;
-        let kind: Ident = content.parse()?;
-        match kind.to_string().as_str() {
-            "PASS" | "NICK" | "USER" | "OPER" | "UserMODE" | "SERVICE" | "QUIT" | "SQUIT"
-            | "JOIN" | "PART" | "ChannelMODE" | "TOPIC" | "NAMES" | "LIST" | "INVITE" | "KICK"
-            | "PRIVMSG" | "NOTICE" | "MOTD" | "LUSERS" | "VERSION" | "STATS" | "LINKS" | "TIME"
-            | "CONNECT" | "TRACE" | "ADMIN" | "INFO" | "SERVLIST" | "SQUERY" | "WHO" | "WHOIS"
-            | "WHOWAS" | "KILL" | "PING" | "PONG" | "ERROR" | "AWAY" | "REHASH" | "DIE"
-            | "RESTART" | "SUMMON" | "USERS" | "WALLOPS" | "USERHOST" | "ISON" | "SAJOIN"
-            | "SAMODE" | "SANICK" | "SAPART" | "SAQUIT" | "NICKSERV" | "CHANSERV" | "OPERSERV"
-            | "BOTSERV" | "HOSTSERV" | "MEMOSERV" | "CAP" | "AUTHENTICATE" | "ACCOUNT"
-            | "METADATA" | "MONITOR" | "BATCH" | "CHGHOST" | "Response" | "Raw" => (),
-            _ => {
-                return Err(content.error(format!(
-                    "expected one of: PASS, NICK, USER, OPER, UserMODE, SERVICE, QUIT, SQUIT
-           , JOIN, PART, ChannelMODE, TOPIC, NAMES, LIST, INVITE, KICK
-           , PRIVMSG, NOTICE, MOTD, LUSERS, VERSION, STATS, LINKS, TIME
-           , CONNECT, TRACE, ADMIN, INFO, SERVLIST, SQUERY, WHO, WHOIS
-           , WHOWAS, KILL, PING, PONG, ERROR, AWAY, REHASH, DIE
-           , RESTART, SUMMON, USERS, WALLOPS, USERHOST, ISON, SAJOIN
-           , SAMODE, SANICK, SAPART, SAQUIT, NICKSERV, CHANSERV, OPERSERV
-           , BOTSERV, HOSTSERV, MEMOSERV, CAP, AUTHENTICATE, ACCOUNT
-           , METADATA, MONITOR, BATCH, CHGHOST, Response, Raw not {}",
-                    kind.to_string()
-                )))
-            }
-        }
-
+        let kinds = parse_kinds(&content)?;
         _token = content.parse()?;
         let function = content.parse()?;
 
+        let mut ignored = false;
+        for option in parse_options(&content, &["ignored"])? {
+            match &option.value {
+                OptionValue::Lit(Lit::Bool(bool)) => ignored = bool.value,
+                value => return Err(syn::Error::new(value.span(), "expected true or false")),
+            }
+        }
+
         Ok(Self {
             asyn: false,
             name,
             description,
-            kind,
+            kinds,
             function,
+            ignored,
         })
     }
 }
 
 pub struct Matcher {
     pub asyn: bool,
+    /// Position among all matchers, used to derive the regex identifier
+    pub index: usize,
     pub name: LitStr,
     pub description: LitStr,
     pub matcher: LitStr,
     pub function: Function,
+    /// Matchers with a higher priority run first
+    pub priority: i64,
+    /// Stop other matchers from running after this one handled a message
+    pub consume: bool,
+    /// Also run on messages starting with the command prefix
+    pub prefixed: bool,
+}
+
+impl Matcher {
+    /// The identifier of the compiled regex, names can contain anything
+    /// so only the ascii alphanumeric parts are kept for readability
+    pub fn regex_ident(&self) -> Ident {
+        let name: String = self
+            .name
+            .value()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        format_ident!("__matcher_{}_{}", self.index, name)
+    }
 }
 
 impl IrcItem for Matcher {
@@ -252,25 +557,46 @@ impl IrcItem for Matcher {
         let name = &self.name;
         let function = &self.function;
 
-        let ident = Ident::new(&name.value(), Span::call_site());
+        let ident = self.regex_ident();
 
-        let call = if self.asyn {
-            quote! {
-                #function(&bot, message.clone()).await
-            }
+        let call = isolate(
+            self.asyn,
+            quote! { Some(&message) },
+            "Matcher",
+            &name,
+            quote! { #function(&bot, catinator::FromContext::from_context(ctx)) },
+        );
+
+        let prefixed = if self.prefixed {
+            quote! {}
         } else {
+            quote! { prefixed || }
+        };
+
+        let consume = if self.consume {
             quote! {
-                #function(&bot, message.clone())
+                if result.is_ok() {
+                    consumed = true;
+                }
             }
+        } else {
+            quote! {}
         };
 
         quote! {
-            if #ident.is_match(text) {
-                debug!(target: "matcher", "{} with {:?}", #name, message);
-                let result = #call;
+            if !(#prefixed consumed) && bot.is_enabled(&message, #name) {
+                let captures: Vec<regex::Captures> = #ident.captures_iter(text).collect();
+                if !captures.is_empty() {
+                    debug!(target: "matcher", "{} with {:?}", #name, message);
+                    let ctx = catinator::Context::new(&bot, &message).with_captures(captures);
+                    let result = #call;
+                    let result = bot.handled(catinator::Kind::Matcher, #name, result);
 
-                if let Err(err) = result {
-                    tracing::warn!("error in matcher: {:?}: {:?}", #name, err)
+                    #consume
+
+                    if let Err(err) = result {
+                        bot.handle_error(Some(&message), catinator::Kind::Matcher, #name, err)
+                    }
                 }
             }
         }
@@ -284,6 +610,19 @@ impl IrcItem for Matcher {
             self.description.value()
         )
     }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let description = &self.description;
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Matcher,
+                name: #name,
+                description: #description,
+            }
+        }
+    }
 }
 
 impl Parse for Matcher {
@@ -301,20 +640,351 @@ impl Parse for Matcher {
         _token = content.parse()?;
         let description = content.parse()?;
         _token = content.parse()?;
-        let matcher = content.parse()?;
+        let matcher: LitStr = content.parse()?;
+        if let Err(err) = regex::Regex::new(&matcher.value()) {
+            return Err(syn::Error::new(
+                matcher.span(),
+                format!("invalid regex: {}", err),
+            ));
+        }
         _token = content.parse()?;
         let function = content.parse()?;
 
+        let mut priority = 0;
+        let mut consume = false;
+        let mut prefixed = false;
+        for option in parse_options(&content, &["priority", "consume", "prefixed"])? {
+            match (option.key.to_string().as_str(), &option.value) {
+                ("priority", OptionValue::Lit(Lit::Int(int))) => priority = int.base10_parse()?,
+                ("consume", OptionValue::Lit(Lit::Bool(bool))) => consume = bool.value,
+                ("prefixed", OptionValue::Lit(Lit::Bool(bool))) => prefixed = bool.value,
+                ("priority", value) => {
+                    return Err(syn::Error::new(value.span(), "expected an integer"))
+                }
+                (_, value) => return Err(syn::Error::new(value.span(), "expected true or false")),
+            }
+        }
+
         Ok(Self {
             asyn: false,
+            index: 0,
             name,
             description,
             matcher,
             function,
+            priority,
+            consume,
+            prefixed,
+        })
+    }
+}
+
+/// When a [Task] runs
+pub enum Schedule {
+    Interval(DurationOption, LitStr),
+    Cron(LitStr),
+}
+
+/// A function run periodically, see `catinator::schedule`
+pub struct Task {
+    pub asyn: bool,
+    pub name: LitStr,
+    pub description: LitStr,
+    pub schedule: Schedule,
+    pub function: Function,
+    /// Variant of `catinator::schedule::Missed`
+    pub missed: Ident,
+}
+
+impl Task {
+    fn parse(input: ParseStream, cron: bool) -> syn::Result<Self> {
+        let content;
+        parenthesized!(content in input);
+
+        let mut _token: Token![,];
+
+        if input.peek(Token![,]) {
+            _token = input.parse()?;
+        }
+
+        let name = content.parse()?;
+        _token = content.parse()?;
+        let description = content.parse()?;
+        _token = content.parse()?;
+        let lit: LitStr = content.parse()?;
+        let schedule = if cron {
+            validate_cron(&lit.value()).map_err(|err| syn::Error::new(lit.span(), err))?;
+            Schedule::Cron(lit)
+        } else {
+            let duration = DurationOption::from_lit(&lit)?;
+            if duration.0 == 0 {
+                return Err(syn::Error::new(lit.span(), "interval must not be zero"));
+            }
+            Schedule::Interval(duration, lit)
+        };
+        _token = content.parse()?;
+        let function = content.parse()?;
+
+        let mut missed = Ident::new("Skip", proc_macro2::Span::call_site());
+        for option in parse_options(&content, &["missed"])? {
+            let value = match &option.value {
+                OptionValue::Ident(ident) => ident.to_string(),
+                _ => String::new(),
+            };
+
+            missed = match value.as_str() {
+                "skip" => Ident::new("Skip", option.value.span()),
+                "burst" => Ident::new("Burst", option.value.span()),
+                "delay" if !cron => Ident::new("Delay", option.value.span()),
+                _ => {
+                    let expected = if cron {
+                        "expected skip or burst"
+                    } else {
+                        "expected skip, burst or delay"
+                    };
+                    return Err(syn::Error::new(option.value.span(), expected));
+                }
+            };
+        }
+
+        Ok(Self {
+            asyn: false,
+            name,
+            description,
+            schedule,
+            function,
+            missed,
+        })
+    }
+
+    /// Register the task with the `__scheduler`
+    pub fn register(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let missed = &self.missed;
+
+        match &self.schedule {
+            Schedule::Interval(duration, _) => {
+                let duration = duration.to_tokens();
+                quote! {
+                    __scheduler.interval(#name, #duration, catinator::schedule::Missed::#missed);
+                }
+            }
+            Schedule::Cron(expression) => quote! {
+                __scheduler
+                    .cron(#name, #expression, catinator::schedule::Missed::#missed)
+                    .expect("cron expression is validated by the catinator macro");
+            },
+        }
+    }
+}
+
+impl IrcItem for Task {
+    fn to_call(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let function = &self.function;
+
+        let call = isolate(
+            self.asyn,
+            quote! { None },
+            "Task",
+            name,
+            quote! { #function(&bot) },
+        );
+
+        quote! {
+            if #name == __task && !bot.is_faulted(#name) {
+                debug!(target: "task", "running {}", #name);
+                let result = #call;
+                let result = bot.handled(catinator::Kind::Task, #name, result);
+
+                if let Err(err) = result {
+                    bot.handle_error(None, catinator::Kind::Task, #name, err)
+                }
+            }
+        }
+    }
+
+    fn help(&self) -> String {
+        let schedule = match &self.schedule {
+            Schedule::Interval(_, period) => format!("every {}", period.value()),
+            Schedule::Cron(expression) => expression.value(),
+        };
+
+        format!(
+            "  {} ({}): {}",
+            self.name.value(),
+            schedule,
+            self.description.value()
+        )
+    }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let description = &self.description;
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Task,
+                name: #name,
+                description: #description,
+            }
+        }
+    }
+}
+
+/// A function receiving events of a type from `catinator::events`
+pub struct Subscriber {
+    pub asyn: bool,
+    pub name: LitStr,
+    pub description: LitStr,
+    pub event: syn::Type,
+    pub function: Function,
+}
+
+impl IrcItem for Subscriber {
+    fn to_call(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let event = &self.event;
+        let function = &self.function;
+
+        let call = isolate(
+            self.asyn,
+            quote! { None },
+            "Subscriber",
+            name,
+            quote! { #function(&bot, event) },
+        );
+
+        quote! {
+            if let (Some(event), false) = (__event.downcast_ref::<#event>(), bot.is_faulted(#name)) {
+                debug!(target: "event", "{} with {}", #name, __event.type_name());
+                let result = #call;
+                let result = bot.handled(catinator::Kind::Subscriber, #name, result);
+
+                if let Err(err) = result {
+                    bot.handle_error(None, catinator::Kind::Subscriber, #name, err)
+                }
+            }
+        }
+    }
+
+    fn help(&self) -> String {
+        let event = &self.event;
+        format!(
+            "  {} ({}): {}",
+            self.name.value(),
+            quote!(#event).to_string().replace(' ', ""),
+            self.description.value()
+        )
+    }
+
+    fn handler(&self) -> proc_macro2::TokenStream {
+        let name = &self.name;
+        let description = &self.description;
+
+        quote! {
+            catinator::Handler {
+                kind: catinator::Kind::Subscriber,
+                name: #name,
+                description: #description,
+            }
+        }
+    }
+}
+
+impl Parse for Subscriber {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        parenthesized!(content in input);
+
+        let mut _token: Token![,];
+
+        if input.peek(Token![,]) {
+            _token = input.parse()?;
+        }
+
+        let name = content.parse()?;
+        _token = content.parse()?;
+        let description = content.parse()?;
+        _token = content.parse()?;
+        let event = content.parse()?;
+        _token = content.parse()?;
+        let function = content.parse()?;
+        parse_options(&content, &[])?;
+
+        Ok(Self {
+            asyn: false,
+            name,
+            description,
+            event,
+            function,
         })
     }
 }
 
+/// Run the `call` of a handler through `Bot::isolate` so panics are caught
+fn isolate(
+    asyn: bool,
+    msg: proc_macro2::TokenStream,
+    kind: &str,
+    name: &impl ToTokens,
+    call: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let kind = format_ident!("{}", kind);
+
+    if asyn {
+        quote! {
+            bot.isolate_async(#msg, catinator::Kind::#kind, #name, async { #call.await }).await
+        }
+    } else {
+        quote! {
+            bot.isolate(#msg, catinator::Kind::#kind, #name, || #call)
+        }
+    }
+}
+
+/// Run `send`, `bot.send_notice(target, ..)?` calls replying to the sender of the message,
+/// isolated and with errors handled like the command `name`
+pub fn notify_sender(
+    name: &impl ToTokens,
+    send: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let call = isolate(
+        false,
+        quote! { Some(&message) },
+        "Command",
+        name,
+        quote! {
+            {
+                let Some(target) = message.source_nickname() else {
+                    return Ok(());
+                };
+                #send
+                Ok(())
+            }
+        },
+    );
+
+    quote! {
+        let result = #call;
+        let result = bot.handled(catinator::Kind::Command, #name, result);
+
+        if let Err(err) = result {
+            bot.handle_error(Some(&message), catinator::Kind::Command, #name, err)
+        }
+    }
+}
+
+fn duration_tokens(duration: &Option<DurationOption>) -> proc_macro2::TokenStream {
+    match duration {
+        Some(duration) => {
+            let duration = duration.to_tokens();
+            quote! { Some(#duration) }
+        }
+        None => quote! { None },
+    }
+}
+
 pub enum Function {
     Path(Path),
     Expr(Punctuated<Ident, Token![.]>),
@@ -334,9 +1004,123 @@ impl Parse for Function {
         if input.peek2(Token![::]) {
             Ok(Function::Path(input.parse()?))
         } else if input.peek2(Token![.]) {
-            Ok(Function::Expr(input.parse_terminated(Ident::parse, Token![.])?))
+            let mut expr = Punctuated::new();
+            expr.push_value(input.parse()?);
+            while input.peek(Token![.]) {
+                expr.push_punct(input.parse()?);
+                expr.push_value(input.parse()?);
+            }
+            Ok(Function::Expr(expr))
+        } else if input.peek(syn::Ident) {
+            Ok(Function::Path(input.parse()?))
         } else {
             Err(input.error("did not find path or dotted"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> syn::Result<Items> {
+        syn::parse_str(input)
+    }
+
+    fn error(input: &str) -> String {
+        parse(input).err().expect("expected an error").to_string()
+    }
+
+    #[test]
+    fn test_matcher_ident() {
+        let items = parse(
+            r#"
+            matcher("url-title", "description", r"https?://", function)
+            matcher("ünicode", "description", r"x", function)
+            "#,
+        )
+        .unwrap();
+
+        let idents: Vec<String> = items
+            .inner
+            .iter()
+            .filter_map(|item| match item {
+                Item::Matcher(matcher) => Some(matcher.regex_ident().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(idents, ["__matcher_0_url_title", "__matcher_1__nicode"]);
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(
+            error(r#"matcher("name", "description", r"(unclosed", function)"#)
+                .starts_with("invalid regex")
+        );
+    }
+
+    #[test]
+    fn test_tasks() {
+        assert!(parse(
+            r#"
+            interval("poll", "description", "30m", function, missed = burst)
+            cron("weekly", "description", "*/15 9-17 * * 1-5", function)
+            "#
+        )
+        .is_ok());
+
+        assert_eq!(
+            error(r#"interval("poll", "description", "0s", function)"#),
+            "interval must not be zero"
+        );
+        assert_eq!(
+            error(r#"interval("poll", "description", "999999999999999d", function)"#),
+            "duration \"999999999999999d\" is too long"
+        );
+        assert_eq!(
+            error(r#"cron("weekly", "description", "0 24 * * *", function)"#),
+            "invalid hour \"24\", expected 0-23"
+        );
+        assert_eq!(
+            error(r#"cron("yearly", "description", "0 0 30 2 *", function)"#),
+            "day \"30\" never occurs in month \"2\""
+        );
+        assert_eq!(
+            error(r#"cron("weekly", "description", "0 9 * * 1", function, missed = delay)"#),
+            "expected skip or burst"
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            error(
+                r#"
+                command("pet", "description", function)
+                matcher("pet", "description", r"x", function)
+                "#
+            ),
+            "duplicate name pet"
+        );
+        assert_eq!(
+            error(
+                r#"
+                group("quote", "description", [
+                    command("add", "description", function)
+                    command("add", "description", function)
+                ])
+                "#
+            ),
+            "duplicate name quote add"
+        );
+        assert_eq!(
+            error(r#"command("help", "description", function)"#),
+            "help is the name of a builtin command"
+        );
+        assert_eq!(
+            error(r#"command("two words", "description", function)"#),
+            "names must not be empty or contain whitespace"
+        );
+    }
+}
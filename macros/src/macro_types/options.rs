@@ -0,0 +1,337 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, Lit, LitStr, Token,
+};
+
+/// A trailing `key = value` option of an item, like `permission = admin`
+pub struct ItemOption {
+    pub key: Ident,
+    pub value: OptionValue,
+}
+
+pub enum OptionValue {
+    Ident(Ident),
+    Lit(Lit),
+}
+
+impl OptionValue {
+    pub fn span(&self) -> Span {
+        match self {
+            OptionValue::Ident(ident) => ident.span(),
+            OptionValue::Lit(lit) => lit.span(),
+        }
+    }
+}
+
+impl Parse for ItemOption {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        let _: Token![=] = input.parse()?;
+
+        let value = if input.peek(syn::Ident) {
+            OptionValue::Ident(input.parse()?)
+        } else {
+            OptionValue::Lit(input.parse()?)
+        };
+
+        Ok(Self { key, value })
+    }
+}
+
+/// Parse the options following the function of an item
+pub fn parse_options(input: ParseStream, allowed: &[&str]) -> syn::Result<Vec<ItemOption>> {
+    let mut options: Vec<ItemOption> = Vec::new();
+
+    while input.peek(Token![,]) {
+        let _: Token![,] = input.parse()?;
+        if input.is_empty() {
+            break;
+        }
+
+        let option: ItemOption = input.parse()?;
+        let key = option.key.to_string();
+
+        if !allowed.contains(&key.as_str()) {
+            return Err(syn::Error::new(
+                option.key.span(),
+                format!(
+                    "unknown option {}, expected one of: {}",
+                    key,
+                    allowed.join(", ")
+                ),
+            ));
+        }
+
+        if options.iter().any(|o| o.key == option.key) {
+            return Err(syn::Error::new(
+                option.key.span(),
+                format!("duplicate option {}", key),
+            ));
+        }
+
+        options.push(option);
+    }
+
+    Ok(options)
+}
+
+const LEVELS: [(&str, &str); 6] = [
+    ("anyone", "Anyone"),
+    ("identified", "Identified"),
+    ("voiced", "Voiced"),
+    ("op", "Op"),
+    ("admin", "Admin"),
+    ("owner", "Owner"),
+];
+
+/// A permission level, see `catinator::permission::Level`
+#[derive(Clone)]
+pub struct Permission(pub String);
+
+impl Permission {
+    pub fn anyone() -> Permission {
+        Permission("anyone".to_string())
+    }
+
+    pub fn from_option(option: &ItemOption) -> syn::Result<Permission> {
+        let value = match &option.value {
+            OptionValue::Ident(ident) => ident.to_string(),
+            OptionValue::Lit(Lit::Str(lit)) => lit.value(),
+            _ => String::new(),
+        };
+
+        if LEVELS.iter().any(|(level, _)| *level == value) {
+            Ok(Permission(value))
+        } else {
+            Err(syn::Error::new(
+                option.value.span(),
+                format!(
+                    "expected a permission level, one of: {}",
+                    LEVELS
+                        .iter()
+                        .map(|(level, _)| *level)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ))
+        }
+    }
+
+    pub fn to_tokens(&self) -> TokenStream {
+        let (_, variant) = LEVELS
+            .iter()
+            .find(|(level, _)| *level == self.0)
+            .expect("permission level was validated while parsing");
+        let variant = Ident::new(variant, Span::call_site());
+
+        quote! { catinator::permission::Level::#variant }
+    }
+}
+
+/// A duration like `"30s"` or `"1h30m"`, parsed at compile time
+pub struct DurationOption(pub u64);
+
+impl DurationOption {
+    pub fn from_lit(lit: &LitStr) -> syn::Result<DurationOption> {
+        parse_duration(&lit.value())
+            .map(DurationOption)
+            .map_err(|err| syn::Error::new(lit.span(), err))
+    }
+
+    pub fn from_option(option: &ItemOption) -> syn::Result<DurationOption> {
+        match &option.value {
+            OptionValue::Lit(Lit::Str(lit)) => DurationOption::from_lit(lit),
+            value => Err(syn::Error::new(
+                value.span(),
+                "expected a duration string like \"30s\" or \"1h30m\"",
+            )),
+        }
+    }
+
+    pub fn to_tokens(&self) -> TokenStream {
+        let millis = self.0;
+        quote! { std::time::Duration::from_millis(#millis) }
+    }
+}
+
+/// Parse a duration into milliseconds, same format as `catinator::util::parse_duration`.
+///
+/// The macros can not depend on the library, the tests check that both agree.
+fn parse_duration(input: &str) -> Result<u64, String> {
+    let mut total: u64 = 0;
+    let mut rest = input.trim();
+
+    if rest.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(|| format!("missing unit in duration {:?}", input))?;
+        let (number, tail) = rest.split_at(split);
+        let number: u64 = number
+            .parse()
+            .map_err(|_| format!("expected a number in duration {:?}", input))?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let millis: u64 = match unit {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            _ => {
+                return Err(format!(
+                    "unknown unit {:?} in duration {:?}, expected one of: ms, s, m, h, d",
+                    unit, input
+                ))
+            }
+        };
+
+        total = number
+            .checked_mul(millis)
+            .and_then(|millis| total.checked_add(millis))
+            .ok_or_else(|| format!("duration {:?} is too long", input))?;
+
+        rest = tail;
+    }
+
+    Ok(total)
+}
+
+/// Check a cron expression, same format as `catinator::schedule::Cron`
+pub fn validate_cron(input: &str) -> Result<(), String> {
+    let fields: Vec<&str> = input.split_whitespace().collect();
+    if fields.len() != 5 {
+        return Err("expected 5 fields: minute hour day month weekday".to_string());
+    }
+
+    let ranges = [
+        ("minute", 0, 59),
+        ("hour", 0, 23),
+        ("day", 1, 31),
+        ("month", 1, 12),
+        ("weekday", 0, 7),
+    ];
+
+    let mut sets = [0u64; 5];
+    for ((field, (name, min, max)), set) in fields.iter().zip(ranges).zip(&mut sets) {
+        for part in field.split(',') {
+            let invalid = || format!("invalid {} {:?}, expected {}-{}", name, part, min, max);
+            let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, number(step)?),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                range => match range.split_once('-') {
+                    Some((start, end)) => (number(start)?, number(end)?),
+                    None if part.contains('/') => (number(range)?, max),
+                    None => (number(range)?, number(range)?),
+                },
+            };
+
+            if start < min || end > max || start > end || step == 0 {
+                return Err(invalid());
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                *set |= 1 << value;
+            }
+        }
+    }
+
+    // a restricted day has to exist in one of the months, unless the weekday can match
+    let [_, _, days, months, _] = sets;
+    let lengths = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+    let exists = (1..=12)
+        .any(|month| months & 1 << month != 0 && days & ((1 << (lengths[month - 1] + 1)) - 1) != 0);
+    if fields[2] != "*" && fields[4] == "*" && !exists {
+        return Err(format!(
+            "day {:?} never occurs in month {:?}",
+            fields[2], fields[3]
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// The macro validates at compile time what the library parses at runtime,
+    /// both have to accept and reject the same input
+    #[test]
+    fn test_parse_duration_agrees() {
+        for input in [
+            "30s",
+            "1h30m",
+            "1d2h",
+            "250ms",
+            "0s",
+            "",
+            "30",
+            "5 minutes",
+            "m",
+            "1w",
+            "999999999999999d",
+            "18446744073709551615ms",
+        ] {
+            match (
+                parse_duration(input),
+                catinator::util::parse_duration(input),
+            ) {
+                (Ok(millis), Ok(duration)) => {
+                    assert_eq!(Duration::from_millis(millis), duration, "{:?}", input)
+                }
+                (Err(_), Err(_)) => (),
+                (ours, theirs) => panic!("{:?}: {:?} but {:?}", input, ours, theirs),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_cron_agrees() {
+        for input in [
+            "* * * * *",
+            "*/15 9-17 * * 1-5",
+            "0 0 1,15 * 0",
+            "5/10 * * * *",
+            "0 24 * * *",
+            "60 * * * *",
+            "0 0 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * *",
+            "a * * * *",
+            "0 0 31 2 *",
+            "0 0 30 2 *",
+            "0 0 31 2,3 *",
+            "0 0 29 2 *",
+            "0 0 31 2 1",
+        ] {
+            assert_eq!(
+                validate_cron(input).is_ok(),
+                input.parse::<catinator::schedule::Cron>().is_ok(),
+                "{:?}",
+                input
+            );
+        }
+    }
+}
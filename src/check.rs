@@ -0,0 +1,202 @@
+//! Validate the config before deploying with `catinator check-config`.
+//!
+//! The config is loaded like in [Bot::new](crate::Bot::new) for the selected profile and
+//! every resolved value is printed with the provider that supplied it, or `default` if
+//! it is not set. Passwords, keys and tokens are redacted. Errors are printed with the
+//! key and file they come from and the command exits with a non-zero status.
+//!
+//! ```text
+//! $ catinator check-config --profile release
+//! server.hostname = "irc.snoonet.org"  # TOML file config.toml
+//! server.port = 6697  # default
+//! user.password = "<redacted>"  # `CATINATOR_` environment variable(s)
+//! ```
+
+use std::collections::BTreeMap;
+
+use figment::{value::Value, Figment};
+
+use crate::{config::Config, secrets::is_secret};
+
+/// Replaces secret values
+const REDACTED: &str = "\"<redacted>\"";
+
+/// A value of the resolved config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resolved {
+    /// Dotted key like `server.hostname`
+    pub key: String,
+    /// The value as it would be written in TOML, redacted if it is a secret
+    pub value: String,
+    /// Name of the provider that supplied the value, like `TOML file config.toml`
+    pub source: String,
+}
+
+/// Render `value` for the output, redacting secrets
+fn render(key: &str, value: &impl serde::Serialize) -> String {
+    if is_secret(key) {
+        return REDACTED.to_string();
+    }
+
+    serde_json::to_string(value).unwrap_or_else(|err| format!("<{}>", err))
+}
+
+/// Join a dotted `key` to the `prefix`
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Collect the values set in the `figment` with their provider
+fn collect_set(
+    figment: &Figment,
+    prefix: &str,
+    value: &Value,
+    out: &mut BTreeMap<String, Resolved>,
+) {
+    match value {
+        Value::Dict(_, dict) => {
+            for (key, value) in dict {
+                collect_set(figment, &join(prefix, key), value, out)
+            }
+        }
+        value => {
+            let source = match figment.get_metadata(value.tag()) {
+                Some(metadata) => match &metadata.source {
+                    Some(source) => format!("{} {}", metadata.name, source),
+                    None => metadata.name.to_string(),
+                },
+                None => "unknown".to_string(),
+            };
+
+            out.insert(
+                prefix.to_string(),
+                Resolved {
+                    key: prefix.to_string(),
+                    value: render(prefix, value),
+                    source,
+                },
+            );
+        }
+    }
+}
+
+/// Collect the values of the `config` that are not set in the figment, so use a default
+fn collect_defaults(prefix: &str, value: &serde_json::Value, out: &mut BTreeMap<String, Resolved>) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object {
+                collect_defaults(&join(prefix, key), value, out)
+            }
+        }
+        serde_json::Value::Null => {}
+        value => {
+            out.entry(prefix.to_string()).or_insert_with(|| Resolved {
+                key: prefix.to_string(),
+                value: render(prefix, value),
+                source: "default".to_string(),
+            });
+        }
+    }
+}
+
+/// Validate the config in `figment` and resolve every value with its source, sorted by key.
+///
+/// Fails with all errors in the config, the `figment::Error` can be iterated over them.
+#[allow(clippy::result_large_err)]
+pub fn resolve(figment: &Figment) -> Result<Vec<Resolved>, figment::Error> {
+    let config: Config = figment.extract()?;
+    let value: Value = figment.extract()?;
+
+    let mut resolved = BTreeMap::new();
+    collect_set(figment, "", &value, &mut resolved);
+
+    let defaults = serde_json::to_value(&config).map_err(|err| err.to_string())?;
+    collect_defaults("", &defaults, &mut resolved);
+
+    Ok(resolved.into_values().collect())
+}
+
+/// Check the config of the `profile`, printing the resolved values or the errors.
+///
+/// Returns false if the config is invalid.
+pub fn check_config(profile: &str) -> bool {
+    let figment = Config::figment_for(profile);
+
+    match resolve(&figment) {
+        Ok(resolved) => {
+            for resolved in resolved {
+                println!(
+                    "{} = {}  # {}",
+                    resolved.key, resolved.value, resolved.source
+                );
+            }
+            eprintln!("config of profile {} is valid", figment.profile());
+            true
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("error: {}", error);
+            }
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Toml};
+
+    const CONFIG: &str = r##"
+    [user]
+    nickname = "catinator"
+    username = "catinator"
+    realname = "catinator"
+    password = "hunter2"
+
+    [server]
+    hostname = "irc.example.com"
+
+    [settings]
+    prefix = "!"
+    "##;
+
+    #[test]
+    fn test_resolve() {
+        let figment =
+            Figment::from(Toml::string(CONFIG)).merge(Toml::string("wa_api_key = 'meow'"));
+        let resolved = resolve(&figment).unwrap();
+        let get = |key: &str| {
+            resolved
+                .iter()
+                .find(|resolved| resolved.key == key)
+                .unwrap_or_else(|| panic!("{} is missing: {:#?}", key, resolved))
+                .clone()
+        };
+
+        assert_eq!(get("server.hostname").value, "\"irc.example.com\"");
+        assert_eq!(get("server.hostname").source, "TOML source string");
+        assert_eq!(get("settings.prefix").value, "\"!\"");
+        assert_eq!(get("server.port").value, "6697");
+        assert_eq!(get("server.port").source, "default");
+        assert_eq!(get("user.password").value, REDACTED);
+        assert_eq!(get("wa_api_key").value, REDACTED);
+        assert!(!resolved
+            .iter()
+            .any(|resolved| resolved.key == "server.password"));
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        let figment =
+            Figment::from(Toml::string(CONFIG)).merge(Toml::string("[server]\nport = 'meow'"));
+        let errors = resolve(&figment).unwrap_err();
+
+        let error = errors.into_iter().next().unwrap();
+        assert_eq!(error.path, ["server", "port"]);
+    }
+}
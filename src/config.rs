@@ -41,9 +41,35 @@
 //! sasl = true
 //!
 //! [default.settings]
-//! # The prefix to use for commands
-//! # Example: ":about"
-//! prefix = ':'
+//! # The prefixes to use for commands, the longest matching prefix is used.
+//! # Example: ":about", "cat about"
+//! prefix = [":", "cat "]
+//! # Allow commands like "catinator: about"
+//! nick_commands = true
+//! # Allow commands without prefix in private queries
+//! private_commands = true
+//! # Hostmasks or services accounts with elevated permissions
+//! owners = ["$a:audron"]
+//! admins = ["*!*@cocaine.farm"]
+//! # Nicks, hostmasks or services accounts that are ignored, like other bots
+//! ignore = ["relaybot", "*!*@bots.example.com"]
+//! # Persist runtime state like disabled hooks
+//! state_file = "state.json"
+//! # Reload the config when the file changes
+//! reload_interval = "10s"
+//!
+//! # Override the prefixes in a channel
+//! [default.settings.channels."#gnulag"]
+//! prefix = ["!"]
+//!
+//! # Override the cooldowns of commands
+//! [default.settings.cooldowns.wa]
+//! user = "30s"
+//! channel = "5s"
+//!
+//! # Configuration of hooks, see below
+//! [default.plugins.wolfram_alpha]
+//! api_key_file = "/run/secrets/wa_api_key"
 //!
 //! [release]
 //! [release.user]
@@ -53,18 +79,35 @@
 //! channels = ["<channel 1>", "<channel 2>"]
 //! ```
 //!
+//! # Secrets
+//!
+//! Passwords and keys can be read from files with a `_file` suffix instead,
+//! like `password_file`, see [secrets](crate::secrets).
+//!
+//! # Reloading
+//!
+//! The config is reloaded without reconnecting on SIGHUP, or when the files change if
+//! `reload_interval` is set. See [reload](crate::reload) for which settings apply live.
+//!
+//! # Environment variables
+//!
+//! Nested keys are separated by a double underscore, like `CATINATOR_USER__PASSWORD` for
+//! `user.password` or `CATINATOR_PLUGINS__WOLFRAM_ALPHA__API_KEY` for
+//! `plugins.wolfram_alpha.api_key`. Variables without a double underscore are split on
+//! every underscore instead, so `CATINATOR_USER_PASSWORD` works for keys without one.
+//!
 //! # Configuration for hooks
 //!
-//! If you write hooks that require some configuration you can use the
-//! [`Bot::figment`](crate::Bot::figment) function to retrieve the figment and extract your own
-//! configuration.
+//! Hooks that require some configuration read it from their own table below `plugins`
+//! with [`Bot::plugin_config`](crate::Bot::plugin_config), like `[default.plugins.wolfram_alpha]`.
+//! Subscribe to [Reloaded](crate::reload::Reloaded) to extract it again after the config
+//! was reloaded.
 //!
 //! ## Example:
 //! ```
 //! use serde::{Serialize, Deserialize};
-//! use anyhow::{Context, Result};
+//! use anyhow::Result;
 //! use irc::client::prelude::*;
-//! use figment::providers::Env;
 //!
 //! use catinator::Bot;
 //!
@@ -72,20 +115,18 @@
 //! // you need to derive serde's Serialize and Deserialize on it
 //! #[derive(Clone, Debug, Deserialize, Serialize)]
 //! pub struct WolframAlpha {
-//!     wa_api_key: String,
+//!     api_key: String,
+//!     // Optional settings use serde defaults
+//!     #[serde(default)]
+//!     short: bool,
 //! }
 //!
 //! impl WolframAlpha {
-//!     // Impl a `new()` function that gets passed a reference to the
-//!     // bot, or just a reference to the figment.
+//!     // Impl a `new()` function that gets passed a reference to the bot
 //!     pub fn new(bot: &Bot) -> Result<WolframAlpha> {
-//!         // Get the figment, we have to clone it here to merge our own env var config in.
-//!         bot.figment
-//!             .clone()
-//!             .merge(Env::prefixed("CATINATOR_"))
-//!             // Extract the config into the return type of this function
-//!             .extract()
-//!             .context("failed to extract wolfram alpha config")
+//!         // Extract `[plugins.wolfram_alpha]`, set with `api_key = "..."` in the
+//!         // config or `CATINATOR_PLUGINS__WOLFRAM_ALPHA__API_KEY`
+//!         bot.plugin_config("wolfram_alpha")
 //!     }
 //!
 //!     pub async fn wa(&self, bot: &Bot, msg: Message) -> Result<()> {
@@ -96,6 +137,10 @@
 //! }
 //! ```
 
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 use figment::{
@@ -182,44 +227,225 @@ const fn default_tls() -> bool {
 /// General settings for the bot
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
 pub struct Settings {
-    /// The prefix used for commands like `:about` (default: ':')
-    #[serde(default = "default_prefix")]
-    pub prefix: char,
+    /// The prefixes used for commands like `:about`, either a single string or a list.
+    /// When multiple prefixes match the longest one is used (default: [":"])
+    #[serde(default = "default_prefix", with = "one_or_many")]
+    pub prefix: Vec<String>,
+    /// Allow invoking commands by addressing the bot with its nick,
+    /// like `catinator: about` (default: true)
+    #[serde(default = "default_true")]
+    pub nick_commands: bool,
+    /// Allow invoking commands without a prefix in private queries,
+    /// like `about` (default: true)
+    #[serde(default = "default_true")]
+    pub private_commands: bool,
+    /// Hostmasks or `$a:account` names of the bots owners,
+    /// see [permission](crate::permission) (default: [])
+    #[serde(default)]
+    pub owners: Vec<String>,
+    /// Hostmasks or `$a:account` names of users allowed to use admin commands,
+    /// see [permission](crate::permission) (default: [])
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// Nicks, hostmasks or `$a:account` names of users whose messages do not trigger
+    /// commands and matchers, like other bots. More can be added at runtime with the
+    /// `ignore` command. Admins are never ignored (default: [])
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// File to persist runtime state like disabled hooks in,
+    /// only kept in memory if unset (default: None)
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+    /// [Cooldown]s by command name, their `user` and `channel` durations override
+    /// the ones set in the [catinator](crate::catinator) macro if set (default: {})
+    #[serde(default)]
+    pub cooldowns: BTreeMap<String, Cooldown>,
+    /// Send a notice to users that hit a cooldown (default: true)
+    #[serde(default = "default_true")]
+    pub cooldown_notice: bool,
+    /// Disable handlers that panicked this many times in a row until they are
+    /// re-enabled with the `enable` command, see [isolation](crate::isolation) (default: None)
+    #[serde(default)]
+    pub panic_limit: Option<u32>,
+    /// Record all irc traffic to this file as JSON lines to replay it later,
+    /// see [record](crate::record) (default: None)
+    #[serde(default)]
+    pub record: Option<PathBuf>,
+    /// Order of [middleware](crate::middleware) by name, listed ones run first
+    /// in the given order, the others after them in the order they were added (default: [])
+    #[serde(default)]
+    pub middleware: Vec<String>,
+    /// Check the config files for changes this often and [reload](crate::reload) them
+    /// when they change, like `5s`. SIGHUP always reloads the config (default: None)
+    #[serde(default, with = "duration", skip_serializing_if = "Option::is_none")]
+    pub reload_interval: Option<Duration>,
+    /// Maximum number of [events](crate::events) waiting to be delivered (default: 256)
+    #[serde(default = "default_event_capacity")]
+    pub event_capacity: usize,
+    /// [ChannelSettings] by channel name (default: {})
+    #[serde(default)]
+    pub channels: BTreeMap<String, ChannelSettings>,
     // pub wa_api_key: String,
 }
 
-const fn default_prefix() -> char {
-    ':'
+fn default_prefix() -> Vec<String> {
+    vec![":".to_string()]
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+const fn default_event_capacity() -> usize {
+    256
+}
+
+/// Settings overriding the general [Settings] in a single channel
+#[derive(Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize)]
+pub struct ChannelSettings {
+    /// The prefixes used for commands in this channel,
+    /// replacing the general [`prefix`](Settings::prefix) (default: None)
+    #[serde(
+        default,
+        with = "one_or_many::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub prefix: Option<Vec<String>>,
+}
+
+/// Time that has to pass between uses of a command.
+///
+/// Durations are written like `30s`, `5m` or `1h30m`.
+/// Admins are not affected by cooldowns.
+#[derive(
+    Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize,
+)]
+pub struct Cooldown {
+    /// Time a single user has to wait between uses (default: None)
+    #[serde(default, with = "duration", skip_serializing_if = "Option::is_none")]
+    pub user: Option<Duration>,
+    /// Time that has to pass between uses in the same channel (default: None)
+    #[serde(default, with = "duration", skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Duration>,
+}
+
+/// (De)serialize durations using [parse_duration](crate::util::parse_duration)
+mod duration {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::util::{format_duration, parse_duration};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_str(&format_duration(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|duration| parse_duration(&duration).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// (De)serialize a list of strings that may also be given as a single string
+mod one_or_many {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    impl From<OneOrMany> for Vec<String> {
+        fn from(value: OneOrMany) -> Self {
+            match value {
+                OneOrMany::One(value) => vec![value],
+                OneOrMany::Many(values) => values,
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(values: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        values.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        OneOrMany::deserialize(deserializer).map(Into::into)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            values: &Option<Vec<String>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            values.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Vec<String>>, D::Error> {
+            Ok(Option::<OneOrMany>::deserialize(deserializer)?.map(Into::into))
+        }
+    }
 }
 
 impl Config {
     /// Allow the configuration to be extracted from any [`figment::Provider`].
+    #[allow(clippy::result_large_err)]
     pub fn from<T: Provider>(provider: T) -> Result<Config, Error> {
         Figment::from(provider).extract()
     }
 
     /// Provide a default provider, a `Figment`.
     pub fn figment() -> Figment {
+        Config::figment_for(Config::PROFILE)
+    }
+
+    /// The profile selected by [Config::figment], `debug` or `release` depending on the build
+    #[cfg(debug_assertions)]
+    pub const PROFILE: &'static str = "debug";
+    /// The profile selected by [Config::figment], `debug` or `release` depending on the build
+    #[cfg(not(debug_assertions))]
+    pub const PROFILE: &'static str = "release";
+
+    /// Provide the default `Figment` with the `profile` selected instead of the one
+    /// of the build, like to [check](crate::check) the release config in a debug build.
+    pub fn figment_for(profile: &str) -> Figment {
         use figment::providers::Env;
 
         let mut figment = Figment::new();
 
-        #[cfg(debug_assertions)]
-        const PROFILE: &str = "debug";
-        #[cfg(not(debug_assertions))]
-        const PROFILE: &str = "release";
-
-        let config_file = if let Ok(path) = std::env::var("CATINATOR_CONFIG") {
-            path
-        } else {
-            "config.toml".to_string()
-        };
+        let config_file = Config::file();
 
-        debug!("using config file: {}", config_file);
+        debug!("using config file: {}", config_file.display());
 
         figment = figment
             .merge(Toml::file(config_file).nested())
-            .merge(Env::prefixed("CATINATOR_").split('_'));
+            .merge(
+                Env::prefixed("CATINATOR_")
+                    .filter(|key| !key.as_str().contains("__"))
+                    .split('_'),
+            )
+            .merge(
+                Env::prefixed("CATINATOR_")
+                    .filter(|key| key.as_str().contains("__"))
+                    .split("__"),
+            );
 
         #[cfg(debug_assertions)]
         {
@@ -227,7 +453,25 @@ impl Config {
             figment = figment.merge(Toml::file("config.debug.toml").nested());
         }
 
-        figment.select(PROFILE)
+        crate::secrets::resolve_files(figment.select(profile))
+    }
+
+    /// The main config file, `CATINATOR_CONFIG` or `config.toml`
+    pub fn file() -> PathBuf {
+        std::env::var_os("CATINATOR_CONFIG")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("config.toml"))
+    }
+
+    /// The config files loaded by [Config::figment], watched by the [reload](crate::reload)
+    pub fn files() -> Vec<PathBuf> {
+        #[allow(unused_mut)]
+        let mut files = vec![Config::file()];
+
+        #[cfg(debug_assertions)]
+        files.push(PathBuf::from("config.debug.toml"));
+
+        files
     }
 }
 
@@ -245,3 +489,92 @@ impl Provider for Config {
         Some(Profile::Default)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(toml: &str) -> Settings {
+        Figment::from(Toml::string(toml)).extract().unwrap()
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(settings("").prefix, vec![":"]);
+        assert_eq!(settings("prefix = '!'").prefix, vec!["!"]);
+        assert_eq!(settings("prefix = [':', 'cat ']").prefix, vec![":", "cat "]);
+
+        let settings = settings(
+            r##"
+            [channels."#gnulag"]
+            prefix = ["!", "::"]
+            [channels."#other"]
+            "##,
+        );
+        assert_eq!(
+            settings.channels["#gnulag"].prefix,
+            Some(vec!["!".to_string(), "::".to_string()])
+        );
+        assert_eq!(settings.channels["#other"].prefix, None);
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn test_env() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env("CATINATOR_PLUGINS__TITLE__MAX_LENGTH", "5");
+            jail.set_env("CATINATOR_SETTINGS_PREFIX", "!");
+
+            let figment = Config::figment_for("debug");
+            assert_eq!(
+                figment.extract_inner::<usize>("plugins.title.max_length")?,
+                5
+            );
+            assert_eq!(figment.extract_inner::<String>("settings.prefix")?, "!");
+            assert!(!figment.contains("plugins.title.max.length"));
+
+            Ok(())
+        });
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    struct Title {
+        #[serde(default)]
+        max_length: Option<usize>,
+        #[serde(default)]
+        enabled: bool,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Required {
+        #[allow(dead_code)]
+        api_key: String,
+    }
+
+    #[tokio::test]
+    async fn test_plugin_config() {
+        let bot = crate::testing::TestBot::with_config("[plugins.title]\nmax_length = 5")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            bot.plugin_config::<Title>("title").unwrap(),
+            Title {
+                max_length: Some(5),
+                enabled: false
+            }
+        );
+        assert_eq!(
+            bot.plugin_config::<Title>("other").unwrap(),
+            Title::default()
+        );
+
+        let err = bot.plugin_config::<Required>("wolfram_alpha").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config for plugin wolfram_alpha, set it in [plugins.wolfram_alpha] \
+             or with CATINATOR_PLUGINS__WOLFRAM_ALPHA__<KEY>"
+        );
+        assert!(format!("{:?}", err).contains("missing field `api_key`"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Run the bot offline in a terminal, for developing hooks without an irc server.
+//!
+//! Every line read from stdin is a PRIVMSG sent by `nick!nick@console` to a channel,
+//! it is dispatched like any message from the server. Everything the bot sends is
+//! printed to stdout instead, with irc formatting rendered as ANSI escape sequences.
+//! The bot stops when stdin is closed.
+//!
+//! ```text
+//! $ catinator --console --nick audron --channel '#cats'
+//! :pet
+//! [#cats] * catinator purrs
+//! ```
+//!
+//! The sender is matched against the [permission](crate::permission) settings like any
+//! other user, add `*!*@console` to the admins to try admin commands.
+
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use irc::client::prelude::*;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::{middleware::Middleware, util::to_ansi, Bot};
+
+/// Host of the console user
+pub const HOST: &str = "console";
+
+/// Build the message for a `line` sent by `nick` to `channel`
+fn privmsg(nick: &str, channel: &str, line: &str) -> Message {
+    Message {
+        tags: None,
+        prefix: Some(Prefix::Nickname(
+            nick.to_string(),
+            nick.to_string(),
+            HOST.to_string(),
+        )),
+        command: Command::PRIVMSG(channel.to_string(), line.to_string()),
+    }
+}
+
+/// Render a message sent by the bot `nick` for the terminal
+fn render(nick: &str, msg: &Message) -> String {
+    match &msg.command {
+        Command::PRIVMSG(target, text) => match text
+            .strip_prefix("\u{001}ACTION ")
+            .and_then(|action| action.strip_suffix('\u{001}'))
+        {
+            Some(action) => format!("[{}] * {} {}", target, nick, to_ansi(action)),
+            None => format!("[{}] <{}> {}", target, nick, to_ansi(text)),
+        },
+        Command::NOTICE(target, text) => format!("[{}] -{}- {}", target, nick, to_ansi(text)),
+        _ => format!("> {}", msg.to_string().trim_end()),
+    }
+}
+
+/// Prints all outgoing messages instead of sending them
+struct Console;
+
+impl Middleware for Console {
+    fn name(&self) -> &str {
+        "console"
+    }
+
+    fn outgoing(&self, bot: &Bot, msg: Message) -> Option<Message> {
+        println!("{}", render(&bot.config.user.nickname, &msg));
+        None
+    }
+}
+
+impl Bot {
+    /// Initializes the bot for the [console](self), reading messages of `nick` in `channel`
+    /// from stdin. The config is loaded like in [Bot::new] but no connection is made.
+    pub async fn console(nick: &str, channel: &str) -> Result<Bot> {
+        let mut bot = Bot::offline(crate::config::Config::figment()).await?;
+        bot.add_middleware(Console);
+
+        let (nick, channel) = (nick.to_string(), channel.to_string());
+        let lines = BufReader::new(tokio::io::stdin()).lines();
+        let incoming = stream::unfold(lines, |mut lines| async move {
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => return Some((line, lines)),
+                    Ok(None) => return None,
+                    Err(err) => {
+                        tracing::error!("failed to read from stdin: {}", err);
+                        return None;
+                    }
+                }
+            }
+        })
+        .map(move |line| privmsg(&nick, &channel, &line));
+
+        *bot.incoming
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner()) = Some(incoming.boxed());
+
+        tracing::info!("running in the console, messages are read from stdin");
+        Ok(bot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_privmsg() {
+        let msg = privmsg("audron", "#cats", ":pet");
+        assert_eq!(msg.source_nickname(), Some("audron"));
+        assert_eq!(
+            msg.to_string(),
+            ":audron!audron@console PRIVMSG #cats ::pet\r\n"
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let render = |command| {
+            render(
+                "catinator",
+                &Message {
+                    tags: None,
+                    prefix: None,
+                    command,
+                },
+            )
+        };
+
+        assert_eq!(
+            render(Command::PRIVMSG("#cats".into(), "meow".into())),
+            "[#cats] <catinator> meow"
+        );
+        assert_eq!(
+            render(Command::PRIVMSG(
+                "#cats".into(),
+                "\u{001}ACTION purrs\u{001}".into()
+            )),
+            "[#cats] * catinator purrs"
+        );
+        assert_eq!(
+            render(Command::NOTICE("audron".into(), "\x02no\x02".into())),
+            "[audron] -catinator- \x1b[1mno\x1b[22m\x1b[0m"
+        );
+        assert_eq!(
+            render(Command::JOIN("#cats".into(), None, None)),
+            "> JOIN #cats"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! The [Context] passed to commands, matchers and hooks.
+//!
+//! Handlers registered with the [catinator](crate::catinator) macro take the bot and
+//! any type implementing [FromContext] as arguments. That is either a [Context],
+//! which provides the commonly needed information about a message and helpers to reply to it,
+//! or the raw [Message] for handlers that need full control.
+//!
+//! ```
+//! use anyhow::Result;
+//! use catinator::{Bot, Context};
+//!
+//! pub fn hello(_bot: &Bot, ctx: Context) -> Result<()> {
+//!     ctx.reply(&format!("hello {}", ctx.nick.unwrap_or("stranger")))
+//! }
+//! ```
+
+use anyhow::{Context as _, Result};
+use irc::client::prelude::*;
+
+use crate::{permission, util, Bot};
+
+/// Information about a received message and helpers to reply to it
+pub struct Context<'a> {
+    /// The bot that received the message
+    pub bot: &'a Bot,
+    /// The raw message
+    pub message: &'a Message,
+    /// Nickname of the sender
+    pub nick: Option<&'a str>,
+    /// Services account of the sender, see [permission::account]
+    pub account: Option<&'a str>,
+    /// Channel the message was sent to, `None` for private messages
+    pub channel: Option<&'a str>,
+    /// Whether the message was sent in a private query with the bot
+    pub private: bool,
+    /// Text of a PRIVMSG or NOTICE, empty for other commands
+    pub text: &'a str,
+    /// Everything following the command name, for commands
+    pub input: &'a str,
+    /// The whitespace separated arguments following the command name, for commands
+    pub args: Vec<&'a str>,
+    /// Captures of every match of the matchers regex in the text, for matchers
+    pub captures: Vec<regex::Captures<'a>>,
+}
+
+impl<'a> Context<'a> {
+    /// Create a new context for `message`
+    pub fn new(bot: &'a Bot, message: &'a Message) -> Context<'a> {
+        let (private, text) = match &message.command {
+            Command::PRIVMSG(target, text) => (!target.is_channel_name(), text.as_str()),
+            Command::NOTICE(_, text) => (false, text.as_str()),
+            _ => (false, ""),
+        };
+
+        Context {
+            bot,
+            message,
+            nick: message.source_nickname(),
+            account: permission::account(message),
+            channel: util::channel(message),
+            private,
+            text,
+            input: "",
+            args: Vec::new(),
+            captures: Vec::new(),
+        }
+    }
+
+    /// Set the `input` following the command name, also splitting it into `args`
+    pub fn with_input(mut self, input: &'a str) -> Context<'a> {
+        self.input = input.trim();
+        self.args = self.input.split_whitespace().collect();
+        self
+    }
+
+    /// Set the `captures` of all matches of the matchers regex
+    pub fn with_captures(mut self, captures: Vec<regex::Captures<'a>>) -> Context<'a> {
+        self.captures = captures;
+        self
+    }
+
+    /// Get the text of the capture group `index` of the first match
+    pub fn capture(&self, index: usize) -> Option<&'a str> {
+        self.captures
+            .first()
+            .and_then(|captures| captures.get(index))
+            .map(|capture| capture.as_str())
+    }
+
+    /// Get the text of the capture group `index` of every match that has it
+    pub fn capture_all(&self, index: usize) -> Vec<&'a str> {
+        self.captures
+            .iter()
+            .filter_map(|captures| captures.get(index))
+            .map(|capture| capture.as_str())
+            .collect()
+    }
+
+    fn response_target(&self) -> Result<&'a str> {
+        self.message
+            .response_target()
+            .context("failed to get response target")
+    }
+
+    fn source_nickname(&self) -> Result<&'a str> {
+        self.nick.context("failed to get source nickname")
+    }
+
+    /// Reply with a privmsg in the channel or private query the message was received in
+    pub fn reply(&self, text: &str) -> Result<()> {
+        Ok(self.bot.send_privmsg(self.response_target()?, text)?)
+    }
+
+    /// Reply with a notice to the sender
+    pub fn reply_notice(&self, text: &str) -> Result<()> {
+        Ok(self.bot.send_notice(self.source_nickname()?, text)?)
+    }
+
+    /// Reply with an action (`/me`) in the channel or private query the message was received in
+    pub fn reply_action(&self, text: &str) -> Result<()> {
+        Ok(self.bot.send_action(self.response_target()?, text)?)
+    }
+
+    /// Reply with a privmsg in a private query with the sender
+    pub fn reply_private(&self, text: &str) -> Result<()> {
+        Ok(self.bot.send_privmsg(self.source_nickname()?, text)?)
+    }
+}
+
+/// Types that can be passed to handlers, see the [module documentation](self)
+pub trait FromContext<'a>: Sized {
+    fn from_context(ctx: Context<'a>) -> Self;
+}
+
+impl<'a> FromContext<'a> for Context<'a> {
+    fn from_context(ctx: Context<'a>) -> Self {
+        ctx
+    }
+}
+
+/// Adapter for handlers taking the raw `Message`
+impl<'a> FromContext<'a> for Message {
+    fn from_context(ctx: Context<'a>) -> Self {
+        ctx.message.clone()
+    }
+}
@@ -0,0 +1,245 @@
+//! Track cooldowns of commands per user and per channel.
+//!
+//! Cooldowns are set with the `cooldown` and `channel_cooldown` options of the
+//! [catinator](crate::catinator) macro or the [`cooldowns`](crate::config::Settings::cooldowns)
+//! setting.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What a cooldown is tracked for
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Scope {
+    User(String),
+    Channel(String),
+}
+
+#[derive(Debug)]
+struct Entry {
+    until: Instant,
+    notified: bool,
+}
+
+/// A command is still on cooldown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Active {
+    /// Time until the command can be used again
+    pub remaining: Duration,
+    /// Whether the user was already told about this cooldown
+    pub notified: bool,
+}
+
+/// Cooldowns of all commands
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    entries: Mutex<HashMap<(String, Scope), Entry>>,
+}
+
+/// Entries are pruned when the map grows beyond this size
+const PRUNE_SIZE: usize = 1024;
+
+impl Cooldowns {
+    pub fn new() -> Cooldowns {
+        Cooldowns::default()
+    }
+
+    /// Check if `name` may be used by `nick` in `channel`, starting its cooldowns if so.
+    ///
+    /// Returns the active cooldown if it may not be used, only the first check
+    /// during a cooldown reports it as not `notified` yet.
+    pub fn check(
+        &self,
+        name: &str,
+        nick: &str,
+        channel: Option<&str>,
+        user_cooldown: Option<Duration>,
+        channel_cooldown: Option<Duration>,
+        now: Instant,
+    ) -> Result<(), Active> {
+        let mut scopes = Vec::with_capacity(2);
+        if let Some(cooldown) = user_cooldown {
+            scopes.push((Scope::User(nick.to_lowercase()), cooldown));
+        }
+        if let (Some(channel), Some(cooldown)) = (channel, channel_cooldown) {
+            scopes.push((Scope::Channel(channel.to_lowercase()), cooldown));
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut active: Option<Active> = None;
+        for (scope, _) in &scopes {
+            if let Some(entry) = entries.get_mut(&(name.to_string(), scope.clone())) {
+                if entry.until > now {
+                    let remaining = entry.until - now;
+                    let notified = entry.notified;
+                    entry.notified = true;
+
+                    active = Some(match active {
+                        Some(active) => Active {
+                            remaining: remaining.max(active.remaining),
+                            notified: notified && active.notified,
+                        },
+                        None => Active {
+                            remaining,
+                            notified,
+                        },
+                    });
+                }
+            }
+        }
+
+        if let Some(active) = active {
+            return Err(active);
+        }
+
+        if entries.len() >= PRUNE_SIZE {
+            entries.retain(|_, entry| entry.until > now);
+        }
+
+        for (scope, cooldown) in scopes {
+            entries.insert(
+                (name.to_string(), scope),
+                Entry {
+                    until: now + cooldown,
+                    notified: false,
+                },
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_user_cooldown() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+
+        assert!(cooldowns
+            .check("wa", "audron", Some("#chan"), Some(10 * SECOND), None, now)
+            .is_ok());
+
+        let active = cooldowns
+            .check(
+                "wa",
+                "Audron",
+                Some("#other"),
+                Some(10 * SECOND),
+                None,
+                now + SECOND,
+            )
+            .unwrap_err();
+        assert_eq!(active.remaining, 9 * SECOND);
+        assert!(!active.notified);
+
+        let active = cooldowns
+            .check(
+                "wa",
+                "audron",
+                Some("#chan"),
+                Some(10 * SECOND),
+                None,
+                now + SECOND,
+            )
+            .unwrap_err();
+        assert!(active.notified);
+
+        // other users and commands are not affected
+        assert!(cooldowns
+            .check(
+                "wa",
+                "basso",
+                Some("#chan"),
+                Some(10 * SECOND),
+                None,
+                now + SECOND
+            )
+            .is_ok());
+        assert!(cooldowns
+            .check(
+                "hal",
+                "audron",
+                Some("#chan"),
+                Some(10 * SECOND),
+                None,
+                now + SECOND
+            )
+            .is_ok());
+
+        assert!(cooldowns
+            .check(
+                "wa",
+                "audron",
+                Some("#chan"),
+                Some(10 * SECOND),
+                None,
+                now + 10 * SECOND
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_channel_cooldown() {
+        let cooldowns = Cooldowns::new();
+        let now = Instant::now();
+
+        assert!(cooldowns
+            .check("pet", "audron", Some("#chan"), None, Some(5 * SECOND), now)
+            .is_ok());
+        assert!(cooldowns
+            .check(
+                "pet",
+                "basso",
+                Some("#chan"),
+                None,
+                Some(5 * SECOND),
+                now + SECOND
+            )
+            .is_err());
+        assert!(cooldowns
+            .check(
+                "pet",
+                "basso",
+                Some("#other"),
+                None,
+                Some(5 * SECOND),
+                now + SECOND
+            )
+            .is_ok());
+        // private messages have no channel cooldown
+        assert!(cooldowns
+            .check("pet", "basso", None, None, Some(5 * SECOND), now + SECOND)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_partial_override() {
+        use crate::testing::{privmsg, TestBot};
+
+        let bot = TestBot::with_config("[settings.cooldowns.pet]\nuser = '10s'")
+            .await
+            .unwrap();
+        let check = |nick, channel| {
+            bot.check_cooldown(
+                &privmsg(nick, channel, ":pet"),
+                "pet",
+                None,
+                Some(5 * SECOND),
+            )
+        };
+
+        assert!(check("audron", "#cats"));
+        // the channel cooldown of the macro is kept
+        assert!(!check("basso", "#cats"));
+        // the user cooldown is set by the config
+        assert!(!check("audron", "#dogs"));
+        assert!(check("basso", "#dogs"));
+    }
+}
@@ -0,0 +1,236 @@
+//! Helpers used by the event loop generated with the [catinator](crate::catinator) macro.
+//!
+//! # Invoking commands
+//! Commands can be invoked in three ways, the latter two can be turned off in the
+//! [settings](crate::config::Settings):
+//!
+//! ```text
+//! :wa 2+2               with one of the configured prefixes
+//! catinator: wa 2+2     by addressing the bot with its nick followed by `:` or `,`
+//! wa 2+2                without any prefix in a private query
+//! ```
+//!
+//! Multiple prefixes can be configured, globally or per channel. If more than one
+//! of them matches the longest is used, so `::` and `:` can be used side by side.
+
+use irc::client::prelude::*;
+
+use crate::{util::CaseMapping, Bot};
+
+/// A command invocation parsed from the text of a PRIVMSG
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Invocation<'a> {
+    /// Name of the invoked command
+    pub name: &'a str,
+    /// Everything following the command name
+    pub input: &'a str,
+    /// Whether the bot was explicitly addressed with the prefix or its nick.
+    /// Matchers do not run on these messages unless they opt in.
+    pub addressed: bool,
+}
+
+impl<'a> Invocation<'a> {
+    /// Split `text` into the command name and its input
+    fn new(text: &'a str, addressed: bool) -> Option<Invocation<'a>> {
+        let text = text.trim_start();
+        let (name, input) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+
+        if name.is_empty() {
+            return None;
+        }
+
+        Some(Invocation {
+            name,
+            input: input.trim(),
+            addressed,
+        })
+    }
+
+    /// Parse the sub-command of a command group from the input
+    pub fn subcommand(&self) -> Option<Invocation<'a>> {
+        Invocation::new(self.input, self.addressed)
+    }
+}
+
+/// Strip the longest of `prefixes` from `text`.
+///
+/// Prefixes not ending in whitespace have to be directly followed by the command name.
+fn strip_prefix<'a>(text: &'a str, prefixes: &[String]) -> Option<&'a str> {
+    prefixes
+        .iter()
+        .filter(|prefix| !prefix.is_empty())
+        .filter_map(|prefix| {
+            let rest = text.strip_prefix(prefix.as_str())?;
+            let separated = prefix.ends_with(char::is_whitespace);
+            (separated || !rest.starts_with(char::is_whitespace)).then_some((prefix.len(), rest))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, rest)| rest)
+}
+
+/// Check if `text` addresses `nick` like `nick: text` or `nick, text`,
+/// returning the remaining text if so
+fn strip_nick<'a>(text: &'a str, nick: &str, casemapping: CaseMapping) -> Option<&'a str> {
+    let (head, rest) = text.split_at_checked(text.find([':', ','])?)?;
+
+    if casemapping.eq(head, nick) {
+        Some(&rest[1..])
+    } else {
+        None
+    }
+}
+
+impl Bot {
+    /// Parse a command invocation from a PRIVMSG with `text` sent to `target`
+    pub fn parse_command<'a>(&self, target: &str, text: &'a str) -> Option<Invocation<'a>> {
+        let settings = &self.config.settings;
+        let text = text.trim_start();
+
+        if let Some(text) = strip_prefix(text, self.prefixes(Some(target))) {
+            return Invocation::new(text, true);
+        }
+
+        if settings.nick_commands {
+            let nick = self.irc_client.current_nickname();
+            if let Some(text) = strip_nick(text, nick, self.casemapping()) {
+                return Invocation::new(text, true);
+            }
+        }
+
+        if settings.private_commands && !target.is_channel_name() {
+            return Invocation::new(text, false);
+        }
+
+        None
+    }
+
+    /// The command prefixes used in `channel`, the general ones if it has no own
+    /// or is not a channel at all
+    pub fn prefixes(&self, channel: Option<&str>) -> &[String] {
+        let settings = &self.config.settings;
+        let casemapping = self.casemapping();
+
+        channel
+            .filter(|channel| channel.is_channel_name())
+            .and_then(|channel| {
+                settings
+                    .channels
+                    .iter()
+                    .find(|(name, _)| casemapping.eq(name, channel))
+            })
+            .and_then(|(_, channel)| channel.prefix.as_deref())
+            .unwrap_or(&settings.prefix)
+    }
+
+    /// The servers [CaseMapping], defaults to rfc1459 until the server announced it
+    pub fn casemapping(&self) -> CaseMapping {
+        *self
+            .casemapping
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Keep track of server information needed for dispatching, called for every message.
+    ///
+    /// This also applies a nickname requested by a [reload](crate::reload) once the
+    /// server confirmed the change.
+    pub fn observe(&mut self, msg: &Message) {
+        if let Command::NICK(nickname) = &msg.command {
+            let own = msg
+                .source_nickname()
+                .is_some_and(|source| self.casemapping().eq(source, &self.config.user.nickname));
+
+            if own {
+                tracing::info!("nickname changed to {}", nickname);
+                self.config.user.nickname = nickname.clone();
+                self.figment = std::mem::take(&mut self.figment).merge(
+                    figment::providers::Serialized::global("user.nickname", nickname),
+                );
+            }
+        }
+
+        if let Command::Response(Response::RPL_ISUPPORT, args) = &msg.command {
+            let casemapping = args
+                .iter()
+                .find_map(|arg| arg.strip_prefix("CASEMAPPING="))
+                .map(CaseMapping::from_isupport);
+
+            if let Some(casemapping) = casemapping {
+                tracing::debug!("server uses casemapping {:?}", casemapping);
+                *self
+                    .casemapping
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner()) = casemapping;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invocation() {
+        assert_eq!(
+            Invocation::new("wa  2 + 2 ", true),
+            Some(Invocation {
+                name: "wa",
+                input: "2 + 2",
+                addressed: true
+            })
+        );
+        assert_eq!(
+            Invocation::new(" pet", false),
+            Some(Invocation {
+                name: "pet",
+                input: "",
+                addressed: false
+            })
+        );
+        assert_eq!(Invocation::new("  ", true), None);
+
+        let invocation = Invocation::new("quote add <audron> meow", true).unwrap();
+        assert_eq!(
+            invocation.subcommand(),
+            Some(Invocation {
+                name: "add",
+                input: "<audron> meow",
+                addressed: true
+            })
+        );
+        assert_eq!(Invocation::new("quote", true).unwrap().subcommand(), None);
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let prefixes = [":", "::", "cat ", ""].map(String::from);
+
+        assert_eq!(strip_prefix(":wa 2+2", &prefixes), Some("wa 2+2"));
+        assert_eq!(strip_prefix("::wa 2+2", &prefixes), Some("wa 2+2"));
+        assert_eq!(strip_prefix(":::wa", &prefixes), Some(":wa"));
+        assert_eq!(strip_prefix("cat pet", &prefixes), Some("pet"));
+        assert_eq!(strip_prefix(": pet", &prefixes), None);
+        assert_eq!(strip_prefix("catpet", &prefixes), None);
+        assert_eq!(strip_prefix("pet", &prefixes), None);
+        assert_eq!(strip_prefix(":wa", &[]), None);
+    }
+
+    #[test]
+    fn test_strip_nick() {
+        let nick = "\\__{^-_-^}";
+        let casemapping = CaseMapping::Rfc1459;
+
+        assert_eq!(
+            strip_nick("\\__{^-_-^}: wa 2+2", nick, casemapping),
+            Some(" wa 2+2")
+        );
+        assert_eq!(
+            strip_nick("|__[~-_-~], pet", nick, casemapping),
+            Some(" pet")
+        );
+        assert_eq!(strip_nick("audron: pet", nick, casemapping), None);
+        assert_eq!(strip_nick("\\__{^-_-^} pet", nick, casemapping), None);
+        assert_eq!(strip_nick("no separator", nick, casemapping), None);
+    }
+}
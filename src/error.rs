@@ -0,0 +1,106 @@
+//! Errors returned by commands, matchers and hooks.
+//!
+//! Errors are reported back to the user that triggered the handler.
+//! A [UserError] is sent to them as is, it is meant for mistakes like missing
+//! input the user can fix themselves. All other errors are internal, the user
+//! only gets an apology with a short correlation id that is also logged with
+//! the full error.
+//!
+//! ```
+//! use anyhow::{bail, Result};
+//! use catinator::{error::UserError, Bot, Context};
+//!
+//! pub fn echo(_bot: &Bot, ctx: Context) -> Result<()> {
+//!     if ctx.input.is_empty() {
+//!         bail!(UserError::new("usage: echo <text>"));
+//!     }
+//!
+//!     ctx.reply(ctx.input)
+//! }
+//! ```
+
+use irc::client::prelude::*;
+
+use crate::{Bot, Kind};
+
+/// An error caused by the user, its message is sent back to them
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("{0}")]
+pub struct UserError(String);
+
+impl UserError {
+    pub fn new(message: impl Into<String>) -> UserError {
+        UserError(message.into())
+    }
+}
+
+/// Find a [UserError] in the chain of `err`
+pub fn user_error(err: &anyhow::Error) -> Option<&UserError> {
+    err.chain().find_map(|err| err.downcast_ref::<UserError>())
+}
+
+/// Generate a short id to find an error in the logs
+fn correlation_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+impl Bot {
+    /// Report the error `err` returned by the handler `name` for `msg`,
+    /// `msg` is `None` for tasks.
+    ///
+    /// User errors are sent to the sender as a notice. Internal errors are logged
+    /// with a correlation id, which is only sent to the sender for commands
+    /// to not have passive matchers and hooks spam channels.
+    pub fn handle_error(&self, msg: Option<&Message>, kind: Kind, name: &str, err: anyhow::Error) {
+        let nick = msg.and_then(|msg| msg.source_nickname());
+
+        let reply = match user_error(&err) {
+            Some(user_error) => {
+                tracing::debug!("user error in {:?} {}: {}", kind, name, user_error);
+                Some(user_error.to_string())
+            }
+            None => {
+                let id = correlation_id();
+                tracing::warn!("error in {:?} {} [{}]: {:?}", kind, name, id, err);
+
+                (kind == Kind::Command).then(|| format!("sorry, something went wrong [{}]", id))
+            }
+        };
+
+        if let (Some(nick), Some(reply)) = (nick, reply) {
+            if let Err(err) = self.send_notice(nick, &reply) {
+                tracing::warn!("failed to send error reply: {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn test_user_error() {
+        let err = anyhow::Error::new(UserError::new("usage: wa <query>"));
+        assert_eq!(
+            user_error(&err).map(ToString::to_string).as_deref(),
+            Some("usage: wa <query>")
+        );
+
+        let err = Err::<(), _>(UserError::new("no input"))
+            .context("wa failed")
+            .unwrap_err();
+        assert_eq!(user_error(&err), Some(&UserError::new("no input")));
+
+        assert_eq!(user_error(&anyhow!("internal")), None);
+    }
+
+    #[test]
+    fn test_correlation_id() {
+        let id = correlation_id();
+        assert_eq!(id.len(), 8);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+}
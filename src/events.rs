@@ -0,0 +1,177 @@
+//! A typed publish/subscribe bus for hooks to talk to each other.
+//!
+//! Any `Send + 'static` type can be emitted as an event with [Bot::emit](crate::Bot::emit).
+//! Events are delivered by the event loop of the [catinator](crate::catinator) macro to
+//! every `subscribe` item registered for their type, in between handling messages.
+//!
+//! ```
+//! use anyhow::Result;
+//! use catinator::{Bot, Context};
+//!
+//! #[derive(Debug)]
+//! pub struct TitleResolved {
+//!     pub url: String,
+//!     pub title: String,
+//! }
+//!
+//! // matcher("title", "description", r"https?://\S+", title)
+//! pub fn title(bot: &Bot, ctx: Context) -> Result<()> {
+//!     let url = ctx.capture(0).unwrap_or_default().to_string();
+//!     bot.emit(TitleResolved { url, title: "meow".to_string() })
+//! }
+//!
+//! // subscribe("log_title", "description", TitleResolved, log_title)
+//! pub fn log_title(_bot: &Bot, event: &TitleResolved) -> Result<()> {
+//!     tracing::info!("{} is {}", event.url, event.title);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Backpressure
+//! The bus holds at most [`event_capacity`](crate::config::Settings::event_capacity) events.
+//! Handlers run on the event loop itself, so [Bot::emit](crate::Bot::emit) never waits and
+//! returns an error if the bus is full. Spawned tasks can use an [EventSender] to wait
+//! for room instead.
+
+use std::any::{Any, TypeId};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use tokio::sync::mpsc;
+
+/// An event emitted on the bus
+pub struct Event {
+    type_name: &'static str,
+    payload: Box<dyn Any + Send>,
+}
+
+impl Event {
+    pub fn new<T: Any + Send>(event: T) -> Event {
+        Event {
+            type_name: std::any::type_name::<T>(),
+            payload: Box::new(event),
+        }
+    }
+
+    /// Get the event if it is of type `T`
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.payload.downcast_ref()
+    }
+
+    /// Check if the event is of type `T`
+    pub fn is<T: Any>(&self) -> bool {
+        (*self.payload).type_id() == TypeId::of::<T>()
+    }
+
+    /// Name of the events type, for logging
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl std::fmt::Debug for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Event")
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Emits events from outside the event loop, like spawned tasks
+#[derive(Clone, Debug)]
+pub struct EventSender(mpsc::Sender<Event>);
+
+impl EventSender {
+    /// Emit `event`, waiting for room on the bus if it is full
+    pub async fn send<T: Any + Send>(&self, event: T) -> Result<()> {
+        self.0
+            .send(Event::new(event))
+            .await
+            .map_err(|err| anyhow!("event bus is closed, dropping {}", err.0.type_name))
+    }
+}
+
+/// The event bus of a [Bot](crate::Bot)
+#[derive(Debug)]
+pub struct Events {
+    sender: mpsc::Sender<Event>,
+    receiver: Mutex<Option<mpsc::Receiver<Event>>>,
+}
+
+impl Events {
+    /// Create a bus holding at most `capacity` events
+    pub fn new(capacity: usize) -> Events {
+        let (sender, receiver) = mpsc::channel(capacity.max(1));
+        Events {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /// Emit `event` without waiting, fails if the bus is full
+    pub fn emit<T: Any + Send>(&self, event: T) -> Result<()> {
+        self.sender
+            .try_send(Event::new(event))
+            .map_err(|err| match err {
+                mpsc::error::TrySendError::Full(event) => {
+                    anyhow!("event bus is full, dropping {}", event.type_name)
+                }
+                mpsc::error::TrySendError::Closed(event) => {
+                    anyhow!("event bus is closed, dropping {}", event.type_name)
+                }
+            })
+    }
+
+    /// Get a sender that can wait for room on the bus
+    pub fn sender(&self) -> EventSender {
+        EventSender(self.sender.clone())
+    }
+
+    /// Take the receiving end of the bus, only the event loop should do this.
+    ///
+    /// Returns `None` if it was already taken.
+    pub fn take_receiver(&self) -> Option<mpsc::Receiver<Event>> {
+        self.receiver
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct NickChanged(String);
+
+    #[tokio::test]
+    async fn test_events() {
+        let events = Events::new(2);
+        let mut receiver = events.take_receiver().unwrap();
+        assert!(events.take_receiver().is_none());
+
+        events.emit(NickChanged("audron".to_string())).unwrap();
+        events.emit(42u32).unwrap();
+        // the bus is full
+        assert!(events.emit(1u32).is_err());
+
+        let event = receiver.recv().await.unwrap();
+        assert!(event.is::<NickChanged>());
+        assert_eq!(
+            event.downcast_ref::<NickChanged>(),
+            Some(&NickChanged("audron".to_string()))
+        );
+        assert_eq!(event.downcast_ref::<u32>(), None);
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.downcast_ref::<u32>(), Some(&42));
+        assert_eq!(event.type_name(), "u32");
+
+        events.sender().send(7u32).await.unwrap();
+        assert_eq!(
+            receiver.recv().await.unwrap().downcast_ref::<u32>(),
+            Some(&7)
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Commands to manage the bot at runtime.
+//!
+//! These are added by the [catinator](crate::catinator) macro automatically,
+//! `enable` and `disable` require the [op](crate::permission::Level::Op) permission level,
+//! `ignore` and `unignore` the [admin](crate::permission::Level::Admin) level,
+//! as does enabling a handler that was disabled for panicking.
+//!
+//! Commands given another `#channel` than the one they are sent in check the
+//! level of the user in that channel instead, admins can manage every channel.
+
+use anyhow::Result;
+use irc::client::prelude::ChannelExt;
+
+use crate::{permission::Level, Bot, Context, Kind};
+
+/// Disable a command, matcher or hook in a channel
+///
+/// `:disable <name> [#channel]`, sub-commands of groups are named like `quote add`,
+/// disabling the group disables all of its sub-commands.
+pub fn disable(bot: &Bot, ctx: Context) -> Result<()> {
+    toggle(bot, ctx, false)
+}
+
+/// Enable a previously disabled command, matcher or hook in a channel
+///
+/// `:enable <name> [#channel]`, handlers disabled for panicking are enabled everywhere
+/// with `:enable <name>`, which requires the admin level.
+pub fn enable(bot: &Bot, ctx: Context) -> Result<()> {
+    toggle(bot, ctx, true)
+}
+
+fn toggle(bot: &Bot, ctx: Context, enabled: bool) -> Result<()> {
+    let state = if enabled { "enabled" } else { "disabled" };
+    let command = if enabled { "enable" } else { "disable" };
+
+    let (name, channel) = match ctx.args.split_last() {
+        Some((last, name)) if last.is_channel_name() && !name.is_empty() => {
+            (name.join(" "), Some(*last))
+        }
+        _ => (ctx.args.join(" "), ctx.channel),
+    };
+
+    if enabled && bot.is_faulted(&name) {
+        let level = bot.permission(ctx.message);
+        if level < Level::Admin {
+            tracing::info!(
+                target: "permission",
+                "denied resetting panics of {} for {:?} with level {}",
+                name,
+                ctx.message.prefix,
+                level
+            );
+            return ctx.reply_notice(&format!(
+                "{} was disabled for panicking, enabling it requires {} permissions",
+                name,
+                Level::Admin
+            ));
+        }
+
+        bot.reset_panics(&name);
+        tracing::info!("reset panics of {} by {:?}", name, ctx.nick);
+        return ctx.reply_notice(&format!("{} is no longer disabled for panicking", name));
+    }
+
+    let (name, channel) = match (name.as_str(), channel) {
+        (name, Some(channel)) if !name.is_empty() => (name, channel),
+        _ => {
+            return ctx.reply_notice(&format!("usage: {} <name> [#channel]", command));
+        }
+    };
+
+    if !may_manage(bot, &ctx, command, channel)? {
+        return Ok(());
+    }
+
+    if !bot.handlers.iter().any(|handler| {
+        handler.name == name && !matches!(handler.kind, Kind::Task | Kind::Subscriber)
+    }) {
+        return ctx.reply_notice(&format!(
+            "there is no command, matcher or hook named {}",
+            name
+        ));
+    }
+
+    if bot
+        .state
+        .set_enabled(channel, name, enabled, bot.casemapping())?
+    {
+        tracing::info!("{} {} in {} by {:?}", state, name, channel, ctx.nick);
+        ctx.reply_notice(&format!("{} is now {} in {}", name, state, channel))
+    } else {
+        ctx.reply_notice(&format!("{} is already {} in {}", name, state, channel))
+    }
+}
+
+/// Check if the sender may use `command` on `channel`, telling them if not.
+///
+/// The level of the channel the command was sent in was already checked by the
+/// dispatcher, other channels require op in that channel or admin.
+fn may_manage(bot: &Bot, ctx: &Context, command: &str, channel: &str) -> Result<bool> {
+    if ctx
+        .channel
+        .is_some_and(|current| bot.casemapping().eq(current, channel))
+    {
+        return Ok(true);
+    }
+
+    let level = bot.permission_in(ctx.message, Some(channel));
+    if level >= Level::Op {
+        return Ok(true);
+    }
+
+    tracing::info!(
+        target: "permission",
+        "denied {} in {} for {:?} with level {}, requires {}",
+        command,
+        channel,
+        ctx.message.prefix,
+        level,
+        Level::Op
+    );
+    ctx.reply_notice(&format!(
+        "{} requires {} permissions in {}",
+        command,
+        Level::Op,
+        channel
+    ))?;
+
+    Ok(false)
+}
+
+/// List all commands, matchers and hooks and if they are disabled in a channel
+///
+/// `:hooks [#channel]`
+pub fn hooks(bot: &Bot, ctx: Context) -> Result<()> {
+    let target = ctx.args.first().copied();
+    if let Some(target) = target {
+        if !may_manage(bot, &ctx, "hooks", target)? {
+            return Ok(());
+        }
+    }
+
+    let channel = target.or(ctx.channel);
+    let disabled = channel
+        .map(|channel| bot.state.disabled(channel, bot.casemapping()))
+        .unwrap_or_default();
+
+    for (kind, title) in [
+        (Kind::Command, "COMMANDS"),
+        (Kind::Matcher, "MATCHERS"),
+        (Kind::Hook, "HOOKS"),
+        (Kind::Task, "TASKS"),
+        (Kind::Subscriber, "SUBSCRIBERS"),
+    ] {
+        let names = bot
+            .handlers
+            .iter()
+            .filter(|handler| handler.kind == kind)
+            .map(|handler| {
+                if bot.is_faulted(handler.name) {
+                    format!("{} (panicked)", handler.name)
+                } else if disabled.contains(handler.name) {
+                    format!("{} (disabled)", handler.name)
+                } else {
+                    handler.name.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        ctx.reply_notice(&format!("{}: {}", title, names))?;
+    }
+
+    Ok(())
+}
+
+/// Ignore a nick, hostmask or account, or list everything that is ignored
+///
+/// `:ignore [nick|hostmask|$a:account]`
+pub fn ignore(bot: &Bot, ctx: Context) -> Result<()> {
+    let entry = match ctx.args.first() {
+        Some(entry) => *entry,
+        None => {
+            let ignored = bot
+                .config
+                .settings
+                .ignore
+                .iter()
+                .cloned()
+                .chain(bot.state.ignored())
+                .collect::<Vec<String>>();
+
+            return if ignored.is_empty() {
+                ctx.reply_notice("nobody is ignored")
+            } else {
+                ctx.reply_notice(&format!("ignored: {}", ignored.join(", ")))
+            };
+        }
+    };
+
+    if bot.state.set_ignored(entry, true)? {
+        tracing::info!("ignoring {} by {:?}", entry, ctx.nick);
+        ctx.reply_notice(&format!("now ignoring {}", entry))
+    } else {
+        ctx.reply_notice(&format!("{} is already ignored", entry))
+    }
+}
+
+/// Stop ignoring a nick, hostmask or account added with `ignore`
+///
+/// `:unignore <nick|hostmask|$a:account>`
+pub fn unignore(bot: &Bot, ctx: Context) -> Result<()> {
+    let entry = match ctx.args.first() {
+        Some(entry) => *entry,
+        None => return ctx.reply_notice("usage: unignore <nick|hostmask|$a:account>"),
+    };
+
+    if bot.state.set_ignored(entry, false)? {
+        tracing::info!("unignoring {} by {:?}", entry, ctx.nick);
+        ctx.reply_notice(&format!("no longer ignoring {}", entry))
+    } else if bot
+        .config
+        .settings
+        .ignore
+        .iter()
+        .any(|ignored| ignored.eq_ignore_ascii_case(entry))
+    {
+        ctx.reply_notice(&format!(
+            "{} is ignored in the config and can not be removed at runtime",
+            entry
+        ))
+    } else {
+        ctx.reply_notice(&format!("{} is not ignored", entry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_noticed;
+    use crate::testing::{privmsg, with_account, TestBot};
+
+    #[tokio::test]
+    async fn test_enable_panicked() {
+        let bot = TestBot::with_config("[settings]\npanic_limit = 1\nadmins = ['$a:audron']")
+            .await
+            .unwrap();
+
+        let _ = bot.isolate(None, Kind::Command, "about", || panic!("meow"));
+        assert!(bot.is_faulted("about"));
+
+        let msg = privmsg("basso", "#cats", ":enable about");
+        enable(&bot, Context::new(&bot, &msg).with_input("about")).unwrap();
+        assert_noticed!(
+            bot,
+            "basso",
+            "about was disabled for panicking, enabling it requires admin permissions"
+        );
+        assert!(bot.is_faulted("about"));
+
+        let msg = with_account(privmsg("audron", "#cats", ":enable about"), "audron");
+        enable(&bot, Context::new(&bot, &msg).with_input("about")).unwrap();
+        assert_noticed!(bot, "audron", "about is no longer disabled for panicking");
+        assert!(!bot.is_faulted("about"));
+    }
+}
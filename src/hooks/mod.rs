@@ -1,9 +1,22 @@
 //! The bots hooks, commands and matchers. For explanation of different types see [crate::catinator]
 //!
 //! # Implementing hooks
+//!
+//! Hooks are functions taking the [Bot](crate::Bot) and a [Context](crate::Context)
+//! or the raw [Message], see [crate::context] for details.
+//!
+//! ```
+//! use anyhow::Result;
+//! use catinator::{Bot, Context};
+//!
+//! /// Replies to `:echo <text>` with the text
+//! pub fn echo(_bot: &Bot, ctx: Context) -> Result<()> {
+//!     ctx.reply(ctx.input)
+//! }
+//! ```
 
-use anyhow::Result;
-use base64::{alphabet, engine, Engine};
+use anyhow::{Context as _, Result};
+use base64::{engine, Engine};
 use irc::client::prelude::*;
 
 mod intensify;
@@ -16,55 +29,73 @@ pub use nitter::*;
 pub use pet::*;
 pub use shifty_eyes::*;
 
+pub mod admin;
 pub mod sed;
 pub mod wolfram_alpha;
 
 /// Replies with some information about the bot
-pub fn about(bot: &crate::Bot, msg: Message) -> Result<()> {
-    bot.send_privmsg(
-        msg.response_target().unwrap(),
-        &format!(
-            "{name} is {name} - https://gitlab.com/cocainefarm/gnulag/catinator",
-            name = bot.config.user.nickname
-        )
-        .to_string(),
-    )
-    .unwrap();
-
-    Ok(())
+pub fn about(bot: &crate::Bot, ctx: crate::Context) -> Result<()> {
+    ctx.reply(&format!(
+        "{name} is {name} - https://gitlab.com/cocainefarm/gnulag/catinator",
+        name = bot.config.user.nickname
+    ))
 }
 
-const ENGINE: engine::GeneralPurpose =
-    engine::GeneralPurpose::new(&alphabet::URL_SAFE, engine::general_purpose::NO_PAD);
+/// SASL payloads are standard base64 with padding
+const ENGINE: engine::GeneralPurpose = engine::general_purpose::STANDARD;
 
-/// Listen to AUTHENTICATE messages and perform SASL authentication
-pub fn sasl(bot: &crate::Bot, msg: Message) -> Result<()> {
-    match msg.command {
-        Command::AUTHENTICATE(text) => {
-            use sasl::client::mechanisms::Plain;
-            use sasl::client::Mechanism;
-            use sasl::common::Credentials;
+/// The encoded SASL PLAIN payload authenticating `username` with `password`
+fn plain_payload(username: &str, password: &str) -> Result<String> {
+    use sasl::client::mechanisms::Plain;
+    use sasl::client::Mechanism;
+    use sasl::common::Credentials;
 
-            if text == "+" {
-                let creds = Credentials::default()
-                    .with_username(bot.config.clone().user.username)
-                    .with_password(bot.config.clone().user.password.unwrap());
+    let creds = Credentials::default()
+        .with_username(username)
+        .with_password(password);
 
-                let mut mechanism = Plain::from_credentials(creds)?;
+    let mut mechanism = Plain::from_credentials(creds)?;
+    Ok(ENGINE.encode(mechanism.initial()))
+}
 
-                let initial_data = mechanism.initial();
+/// Listen to AUTHENTICATE messages and perform SASL authentication
+pub fn sasl(bot: &crate::Bot, msg: Message) -> Result<()> {
+    if let Command::AUTHENTICATE(text) = msg.command {
+        if text == "+" {
+            let password = bot
+                .config
+                .user
+                .password
+                .as_deref()
+                .context("sasl requires user.password to be set")?;
 
-                bot.irc_client.send_sasl(ENGINE.encode(initial_data))?;
-                bot.irc_client.send(Command::CAP(
-                    None,
-                    irc_proto::command::CapSubCommand::END,
-                    None,
-                    None,
-                ))?;
-            }
+            bot.send(Command::AUTHENTICATE(plain_payload(
+                &bot.config.user.username,
+                password,
+            )?))?;
+            bot.send(Command::CAP(
+                None,
+                irc_proto::command::CapSubCommand::END,
+                None,
+                None,
+            ))?;
         }
-        _ => (),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_payload() {
+        // base64 of "\0catinator\0hunter22", padded and with `+` and `/` unlike url safe base64
+        assert_eq!(
+            plain_payload("catinator", "hunter22").unwrap(),
+            "AGNhdGluYXRvcgBodW50ZXIyMg=="
+        );
+        assert_eq!(plain_payload("cat", "?>?>").unwrap(), "AGNhdAA/Pj8+");
+    }
+}
@@ -1,29 +1,21 @@
-use std::sync::LazyLock;
+use anyhow::Result;
 
-use anyhow::{Context, Result};
-use irc::client::prelude::*;
-use regex::Regex;
-
-static RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"https:\/\/(?:twitter|x)\.com\/(\S+?)(?:\s|$)").unwrap());
+use crate::Context;
 
 const URL: &str = "https://xcancel.com/";
 
-pub fn nitter(bot: &crate::Bot, msg: Message) -> Result<()> {
-    if let Command::PRIVMSG(_, text) = msg.command.clone() {
-        let path = RE
-            .captures(&text)
-            .context("failed to capture twitter url path")?
-            .get(1)
-            .context("failed to get path capture group")?
-            .as_str();
+/// Reply with xcancel links for the twitter urls captured by the matcher,
+/// the first capture group of every match is the url path.
+pub fn nitter(_bot: &crate::Bot, ctx: Context) -> Result<()> {
+    let links = ctx
+        .capture_all(1)
+        .iter()
+        .map(|path| format!("{URL}{path}"))
+        .collect::<Vec<String>>();
 
-        bot.send_privmsg(
-            msg.response_target()
-                .context("failed to get response target")?,
-            format!("get cancled {URL}{path}").as_str(),
-        )?;
+    if links.is_empty() {
+        return Ok(());
     }
 
-    Ok(())
+    ctx.reply(&format!("get cancled {}", links.join(" ")))
 }
@@ -1,9 +1,9 @@
-use anyhow::{Context, Result};
-use irc::client::prelude::*;
-use macros::privmsg;
+use anyhow::{Context as _, Result};
 
 use rand::{prelude::IndexedRandom, rng};
 
+use crate::Context;
+
 const PET_RESPONSE: [&str; 5] = [
     "purrs",
     "meows loudly",
@@ -15,14 +15,10 @@ const PET_RESPONSE: [&str; 5] = [
 /// Pet the cat, get rekt
 ///
 /// Sends some random action when petted.
-pub fn pet(bot: &crate::Bot, msg: Message) -> Result<()> {
-    privmsg!(msg, {
-        bot.send_action(
-            msg.response_target()
-                .context("failed to get response target")?,
-            PET_RESPONSE
-                .choose(&mut rng())
-                .context("failed choosing a pet response")?,
-        )?;
-    })
+pub fn pet(_bot: &crate::Bot, ctx: Context) -> Result<()> {
+    ctx.reply_action(
+        PET_RESPONSE
+            .choose(&mut rng())
+            .context("failed choosing a pet response")?,
+    )
 }
@@ -31,6 +31,12 @@ thread_local!(static RE: regex::Regex = regex::Regex::new(r"^s/").unwrap());
 
 pub struct Sed(HashMap<String, Vec<(String, String)>>);
 
+impl Default for Sed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Sed {
     pub fn new() -> Sed {
         Sed(HashMap::new())
@@ -91,18 +97,14 @@ impl Sed {
                 .iter()
                 .rev()
                 .find(|(_, text)| cmd.regex().is_match(text) && !RE.with(|re| re.is_match(text)))
-                .and_then(|(nick, text)| {
+                .map(|(nick, text)| {
                     if text.starts_with("\x01\x01") {
-                        Some(format!(
-                            "* {}{}",
-                            nick,
-                            cmd.execute(&text.replace("\x01", ""))
-                        ))
+                        format!("* {}{}", nick, cmd.execute(&text.replace("\x01", "")))
                     } else {
-                        Some(format!("<{}> {}", nick, cmd.execute(text)))
+                        format!("<{}> {}", nick, cmd.execute(text))
                     }
                 })
-                .map_or(Err(anyhow!("replace failed")), |v| Ok(v));
+                .ok_or_else(|| anyhow!("replace failed"));
         }
 
         Err(anyhow!("not a privmsg"))
@@ -138,7 +140,7 @@ mod tests {
             .unwrap();
         }
 
-        return sed;
+        sed
     }
 
     #[test]
@@ -211,6 +213,21 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_replace_hook() {
+        use crate::testing::{privmsg, TestBot};
+
+        let bot = TestBot::new().await;
+        let mut sed = Sed::new();
+
+        sed.log(&bot, privmsg("audron", "#cats", "cats are great"))
+            .unwrap();
+        sed.replace(&bot, privmsg("bob", "#cats", "s/great/the best/"))
+            .unwrap();
+
+        crate::assert_replied!(bot, "#cats", "<audron> cats are \x02the best\x02");
+    }
+
     #[test]
     fn test_replace() {
         let mut sed = populate_log();
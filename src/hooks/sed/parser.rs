@@ -41,12 +41,12 @@ impl Command {
             let right = Command::parse_segment(&mut chars)?.bold();
             let flags = Flags::from_chars(&mut chars)?;
 
-            let left = Regex::new(&format!("(?{}){}", flags.to_string(), left))
-                .map_err(|err| ParseError::InvalidRegex(err))?;
+            let left =
+                Regex::new(&format!("(?{}){}", flags, left)).map_err(ParseError::InvalidRegex)?;
 
-            return Ok(Command { left, right, flags });
+            Ok(Command { left, right, flags })
         } else {
-            return Err(ParseError::NotSedCommand);
+            Err(ParseError::NotSedCommand)
         }
     }
 
@@ -55,25 +55,18 @@ impl Command {
 
         let mut chars = input.chars();
 
-        loop {
-            let s = chars.next();
-            let slash = chars.next();
+        while let (Some(s), Some(slash)) = (chars.next(), chars.next()) {
+            if s == 's' && slash == '/' {
+                let left = Command::parse_segment(&mut chars)?;
+                let right = Command::parse_segment(&mut chars)?.bold();
+                let flags = Flags::from_chars(&mut chars)?;
 
-            if s.is_some() && slash.is_some() {
-                if s.unwrap() == 's' && slash.unwrap() == '/' {
-                    let left = Command::parse_segment(&mut chars)?;
-                    let right = Command::parse_segment(&mut chars)?.bold();
-                    let flags = Flags::from_chars(&mut chars)?;
+                let left = Regex::new(&format!("(?{}){}", flags, left))
+                    .map_err(ParseError::InvalidRegex)?;
 
-                    let left = Regex::new(&format!("(?{}){}", flags.to_string(), left))
-                        .map_err(|err| ParseError::InvalidRegex(err))?;
-
-                    commands.push(Command { left, right, flags });
-                } else {
-                    return Err(ParseError::NotSedCommand);
-                }
+                commands.push(Command { left, right, flags });
             } else {
-                break;
+                return Err(ParseError::NotSedCommand);
             }
         }
 
@@ -84,7 +77,7 @@ impl Command {
         let mut last_char = '/';
         let mut output = String::new();
 
-        while let Some(c) = chars.next() {
+        for c in chars.by_ref() {
             if c == '/' && last_char != '\\' {
                 break;
             } else if c == '/' && last_char == '\\' {
@@ -99,15 +92,11 @@ impl Command {
     }
 
     pub fn execute(self, target: &str) -> Cow<'_, str> {
-        let result: Cow<str>;
-
         if self.flags.contains(Flags::GLOBAL) {
-            result = self.left.replace_all(target, self.right);
+            self.left.replace_all(target, self.right)
         } else {
-            result = self.left.replace(target, self.right);
+            self.left.replace(target, self.right)
         }
-
-        return result;
     }
 
     pub fn regex(&self) -> &Regex {
@@ -131,50 +120,50 @@ bitflags! {
     }
 }
 
-impl Flags {
-    pub fn to_string(&self) -> String {
-        let mut result = String::new();
-
-        result.push('m');
+impl std::fmt::Display for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("m")?;
 
         if self.contains(Flags::CASE_INSENSITIVE) {
-            result.push('i');
+            f.write_str("i")?;
         }
 
         if self.contains(Flags::SINGLE_LINE) {
-            result.push('s');
+            f.write_str("s")?;
         }
 
         if self.contains(Flags::UNGREEDY) {
-            result.push('U');
+            f.write_str("U")?;
         }
 
         if self.contains(Flags::EXTENDED) {
-            result.push('x');
+            f.write_str("x")?;
         }
 
-        return result;
+        Ok(())
     }
+}
 
+impl Flags {
     pub fn from_chars(chars: &mut Chars) -> Result<Flags, ParseError> {
         let mut flags: Flags = Flags::empty();
 
-        while let Some(c) = chars.next() {
+        for c in chars.by_ref() {
             match c {
                 'g' => {
-                    flags = flags | Flags::GLOBAL;
+                    flags |= Flags::GLOBAL;
                 }
                 'i' => {
-                    flags = flags | Flags::CASE_INSENSITIVE;
+                    flags |= Flags::CASE_INSENSITIVE;
                 }
                 's' => {
-                    flags = flags | Flags::SINGLE_LINE;
+                    flags |= Flags::SINGLE_LINE;
                 }
                 'U' => {
-                    flags = flags | Flags::UNGREEDY;
+                    flags |= Flags::UNGREEDY;
                 }
                 'x' => {
-                    flags = flags | Flags::EXTENDED;
+                    flags |= Flags::EXTENDED;
                 }
                 ';' => return Ok(flags),
                 _ => return Err(ParseError::InvalidFlag),
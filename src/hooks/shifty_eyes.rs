@@ -1,33 +1,28 @@
-use anyhow::{bail, Context, Result};
-use irc::client::prelude::*;
+use anyhow::{bail, Context as _, Result};
+
+use crate::Context;
 
 const EYES: [char; 7] = ['^', 'v', 'V', '>', '<', 'x', 'X'];
 const NOSE: [char; 7] = ['.', '_', '-', ';', '\'', '"', '~'];
 
 /// you are being watched <.<
-pub fn shifty_eyes(bot: &crate::Bot, msg: Message) -> Result<()> {
-    if let Command::PRIVMSG(_, text) = msg.command.clone() {
-        if text.len() == 3 {
-            let mut chars = text.chars();
-            let mut left = chars.next().context("failed to get next character")?;
-            let middle = chars.next().context("failed to get next character")?;
-            let mut right = chars.next().context("failed to get next character")?;
-
-            if EYES.contains(&left) && NOSE.contains(&middle) && EYES.contains(&right) {
-                left = invert(left)?;
-                right = invert(right)?;
-
-                let mut result = String::new();
-                result.push(left);
-                result.push(middle);
-                result.push(right);
-
-                bot.send_privmsg(
-                    msg.response_target()
-                        .context("failed to get response target")?,
-                    result.as_str(),
-                )?;
-            }
+pub fn shifty_eyes(_bot: &crate::Bot, ctx: Context) -> Result<()> {
+    if ctx.text.len() == 3 {
+        let mut chars = ctx.text.chars();
+        let mut left = chars.next().context("failed to get next character")?;
+        let middle = chars.next().context("failed to get next character")?;
+        let mut right = chars.next().context("failed to get next character")?;
+
+        if EYES.contains(&left) && NOSE.contains(&middle) && EYES.contains(&right) {
+            left = invert(left)?;
+            right = invert(right)?;
+
+            let mut result = String::new();
+            result.push(left);
+            result.push(middle);
+            result.push(right);
+
+            ctx.reply(result.as_str())?;
         }
     }
 
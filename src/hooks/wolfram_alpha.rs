@@ -1,49 +1,63 @@
 //! ask wolfram alpha a query
 
 use crate::util::{quote_plus, truncate};
+use crate::{error::UserError, Context};
 // use crate::util::{url_shorteners::Isgd, UrlShortener};
-use anyhow::{bail, Context, Error, Result};
+use anyhow::{bail, Context as _, Error, Result};
 use figment::providers::Env;
 use futures::join;
-use irc::client::prelude::*;
-use macros::privmsg;
 use reqwest::{get, Url};
 use serde::{Deserialize, Serialize};
 
+/// Config in `[plugins.wolfram_alpha]`
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WolframAlpha {
-    wa_api_key: String,
+    api_key: String,
 }
 
 impl WolframAlpha {
     pub fn new(bot: &crate::Bot) -> Result<WolframAlpha> {
-        bot.figment
-            .clone()
-            .merge(Env::prefixed("CATINATOR_"))
-            .extract()
-            .context("failed to extract wolfram alpha config")
+        if bot.figment().contains("plugins.wolfram_alpha") {
+            return bot.plugin_config("wolfram_alpha");
+        }
+
+        // the key used to be set as `wa_api_key` on the top level
+        let figment =
+            crate::secrets::resolve_files(bot.figment().clone().merge(Env::prefixed("CATINATOR_")));
+        match figment.extract_inner("wa_api_key") {
+            Ok(api_key) => {
+                tracing::warn!(
+                    "wa_api_key is deprecated, set api_key in [plugins.wolfram_alpha] instead"
+                );
+                Ok(WolframAlpha { api_key })
+            }
+            Err(_) => bot.plugin_config("wolfram_alpha"),
+        }
     }
 
-    pub async fn wa(&self, bot: &crate::Bot, msg: Message) -> Result<()> {
-        privmsg!(msg, {
-            let content = get_input_query(text)?;
-            bot.send_privmsg(
-                msg.response_target()
-                    .context("failed to get response target")?,
-                &wa_query(&content, Some(&self.wa_api_key), None).await?,
-            )?;
-        })
+    /// Without an api key, for trying out the bot offline. Queries reply that
+    /// wolfram alpha is not configured.
+    pub fn unconfigured() -> WolframAlpha {
+        WolframAlpha {
+            api_key: String::new(),
+        }
     }
 
-    pub async fn hal(&self, bot: &crate::Bot, msg: Message) -> Result<()> {
-        privmsg!(msg, {
-            let content = get_input_query(text)?;
-            bot.send_privmsg(
-                msg.response_target()
-                    .context("failed to get response target")?,
-                &hal_query(&content, Some(&self.wa_api_key), None).await?,
-            )?;
-        })
+    fn api_key(&self) -> Result<&str> {
+        if self.api_key.is_empty() {
+            bail!(UserError::new("wolfram alpha is not configured"));
+        }
+        Ok(&self.api_key)
+    }
+
+    pub async fn wa(&self, _bot: &crate::Bot, ctx: Context<'_>) -> Result<()> {
+        let content = get_input_query(ctx.input)?;
+        ctx.reply(&wa_query(&content, Some(self.api_key()?), None).await?)
+    }
+
+    pub async fn hal(&self, _bot: &crate::Bot, ctx: Context<'_>) -> Result<()> {
+        let content = get_input_query(ctx.input)?;
+        ctx.reply(&hal_query(&content, Some(self.api_key()?), None).await?)
     }
 }
 
@@ -193,12 +207,11 @@ async fn wa_query(
     ))
 }
 
-fn get_input_query(text: &str) -> Result<String, Error> {
-    let input = text.chars().as_str().splitn(2, " ").collect::<Vec<&str>>();
-    if input.len() != 2 {
-        bail!("Empty input for WA query");
+fn get_input_query(input: &str) -> Result<String, Error> {
+    let content = input.trim();
+    if content.is_empty() {
+        bail!(UserError::new("please provide a query"));
     }
-    let content = input[1].trim();
     Ok(content.to_string())
 }
 
@@ -213,7 +226,7 @@ mod tests {
 
     #[test]
     fn test_input_query_content_retrieval() -> Result<(), Error> {
-        let incoming = ":wa test";
+        let incoming = "test";
         let content = get_input_query(incoming)?;
         assert_eq!(content, "test");
         Ok(())
@@ -221,7 +234,7 @@ mod tests {
 
     #[test]
     fn test_input_query_content_retrieval_with_spaces() -> Result<(), Error> {
-        let incoming = ":wa foo bar";
+        let incoming = " foo bar";
         let content = get_input_query(incoming)?;
         assert_eq!(content, "foo bar");
         Ok(())
@@ -229,12 +242,18 @@ mod tests {
 
     #[test]
     fn test_input_query_content_retrieval_with_more_spaces() -> Result<(), Error> {
-        let incoming = ":wa foo bar baz";
+        let incoming = "foo bar baz ";
         let content = get_input_query(incoming)?;
         assert_eq!(content, "foo bar baz");
         Ok(())
     }
 
+    #[test]
+    fn test_input_query_empty() {
+        let err = get_input_query("  ").unwrap_err();
+        assert!(crate::error::user_error(&err).is_some());
+    }
+
     #[test]
     fn test_clean_result_text() {
         assert_eq!(
@@ -314,4 +333,20 @@ mod tests {
         assert_eq!(res, "No results.");
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_unconfigured() {
+        use crate::testing::{privmsg, TestBot};
+
+        let bot = TestBot::new().await;
+        let msg = privmsg("audron", "#cats", ":wa 2+2");
+        let ctx = crate::Context::new(&bot, &msg).with_input("2+2");
+
+        let err = super::WolframAlpha::unconfigured()
+            .wa(&bot, ctx)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "wolfram alpha is not configured");
+        bot.assert_silent();
+    }
 }
@@ -0,0 +1,186 @@
+//! Isolation of handlers, so a panic in one does not take down the whole bot.
+//!
+//! The [catinator](crate::catinator) macro runs every command, matcher, hook, task and
+//! subscriber through [Bot::isolate] or [Bot::isolate_async]. A panic is caught, logged
+//! with the handler name and message, and turned into a [Panicked] error that is
+//! reported like any other internal error.
+//!
+//! If [`panic_limit`](crate::config::Settings::panic_limit) is set, a handler that panics
+//! that many times in a row is disabled everywhere until an admin re-enables it with the
+//! `enable` command.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::Result;
+use futures::FutureExt;
+use irc::client::prelude::*;
+
+use crate::{Bot, Kind};
+
+/// A handler panicked, the error contains the panic message
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("panicked: {0}")]
+pub struct Panicked(pub String);
+
+/// Get the message of a panic `payload`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Panics in a row by handler name
+#[derive(Debug, Default)]
+pub(crate) struct Panics(Mutex<HashMap<String, u32>>);
+
+impl Panics {
+    fn counts(&self) -> MutexGuard<'_, HashMap<String, u32>> {
+        // the counts are always left consistent, so a poisoned lock is still usable
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Record if the handler `name` panicked, returns the number of panics in a row
+    fn record(&self, name: &str, panicked: bool) -> u32 {
+        let mut counts = self.counts();
+
+        if panicked {
+            let count = counts.entry(name.to_string()).or_default();
+            *count += 1;
+            *count
+        } else {
+            counts.remove(name);
+            0
+        }
+    }
+
+    fn count(&self, name: &str) -> u32 {
+        self.counts().get(name).copied().unwrap_or_default()
+    }
+
+    /// Forget the panics of `name`, returns false if there were none
+    fn reset(&self, name: &str) -> bool {
+        self.counts().remove(name).is_some()
+    }
+}
+
+impl Bot {
+    /// Run the sync handler `name` for `msg`, turning a panic into a [Panicked] error
+    pub fn isolate<F>(
+        &self,
+        msg: Option<&Message>,
+        kind: Kind,
+        name: &str,
+        handler: F,
+    ) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(handler));
+        self.isolated(msg, kind, name, result)
+    }
+
+    /// Run the async handler `name` for `msg`, turning a panic into a [Panicked] error
+    pub async fn isolate_async<F>(
+        &self,
+        msg: Option<&Message>,
+        kind: Kind,
+        name: &str,
+        handler: F,
+    ) -> Result<()>
+    where
+        F: Future<Output = Result<()>>,
+    {
+        let result = AssertUnwindSafe(handler).catch_unwind().await;
+        self.isolated(msg, kind, name, result)
+    }
+
+    fn isolated(
+        &self,
+        msg: Option<&Message>,
+        kind: Kind,
+        name: &str,
+        result: std::thread::Result<Result<()>>,
+    ) -> Result<()> {
+        let payload = match result {
+            Ok(result) => {
+                self.panics.record(name, false);
+                return result;
+            }
+            Err(payload) => payload,
+        };
+
+        let message = panic_message(&*payload);
+        let count = self.panics.record(name, true);
+        tracing::error!("{:?} {} panicked with {:?}: {}", kind, name, msg, message);
+
+        if self.config.settings.panic_limit == Some(count) {
+            tracing::error!(
+                "disabling {:?} {} after {} panics in a row, use enable to re-enable it",
+                kind,
+                name,
+                count
+            );
+        }
+
+        Err(Panicked(message).into())
+    }
+
+    /// Check if the handler `name` is disabled for panicking too often,
+    /// see [`panic_limit`](crate::config::Settings::panic_limit)
+    pub fn is_faulted(&self, name: &str) -> bool {
+        self.config
+            .settings
+            .panic_limit
+            .is_some_and(|limit| self.panics.count(name) >= limit)
+    }
+
+    /// Forget the panics of the handler `name`, re-enabling it if it was faulted.
+    ///
+    /// Returns false if it did not panic.
+    pub fn reset_panics(&self, name: &str) -> bool {
+        self.panics.reset(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("static")).unwrap_err();
+        assert_eq!(panic_message(&*payload), "static");
+
+        let payload = std::panic::catch_unwind(|| panic!("formatted {}", 42)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "formatted 42");
+
+        let payload = std::panic::catch_unwind(|| std::panic::panic_any(42)).unwrap_err();
+        assert_eq!(panic_message(&*payload), "unknown panic");
+    }
+
+    #[test]
+    fn test_panics() {
+        let panics = Panics::default();
+
+        assert_eq!(panics.record("about", true), 1);
+        assert_eq!(panics.record("about", true), 2);
+        assert_eq!(panics.count("about"), 2);
+        assert_eq!(panics.count("sed"), 0);
+
+        // a successful run resets the count
+        assert_eq!(panics.record("about", false), 0);
+        assert_eq!(panics.count("about"), 0);
+
+        panics.record("about", true);
+        assert!(panics.reset("about"));
+        assert!(!panics.reset("about"));
+    }
+}
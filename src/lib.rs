@@ -34,17 +34,56 @@
 #[cfg(all(test, feature = "bench"))]
 extern crate test;
 
-use anyhow::{Context, Result};
+use anyhow::{Context as _, Result};
 
+use futures::stream::{BoxStream, StreamExt};
 use irc::client::prelude::*;
+use tracing::debug;
 
+pub mod check;
 pub mod config;
+pub mod console;
+pub mod context;
+pub mod cooldown;
+pub mod dispatch;
+pub mod error;
+pub mod events;
 pub mod hooks;
+pub mod isolation;
+pub mod middleware;
+pub mod permission;
+pub mod record;
+pub mod reload;
+pub mod schedule;
+pub mod secrets;
+pub mod state;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod util;
 
 // Rexport of the catinator proc macros
 pub use macros::catinator;
 
+pub use context::{Context, FromContext};
+
+/// The kind of a [Handler]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Command,
+    Matcher,
+    Hook,
+    Task,
+    Subscriber,
+}
+
+/// A command, matcher, hook or task registered with the [catinator] macro
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Handler {
+    pub kind: Kind,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
 /// The struct handling bot actions and configuration
 pub struct Bot {
     /// The base config of the bot
@@ -54,6 +93,22 @@ pub struct Bot {
     /// The irc client object, used to send messages etc
     /// It is recommended to use the methods directly on the Bot struct instead.
     pub irc_client: irc::client::Client,
+    /// Runtime state like hooks disabled per channel
+    pub state: state::State,
+    /// The handlers registered with the [catinator] macro
+    pub handlers: Vec<Handler>,
+    /// Cooldowns of commands per user and channel
+    pub cooldowns: cooldown::Cooldowns,
+    /// The [events] bus for hooks to talk to each other
+    pub events: events::Events,
+    middleware: Vec<Box<dyn middleware::Middleware>>,
+    panics: isolation::Panics,
+    incoming: std::sync::Mutex<Option<BoxStream<'static, Message>>>,
+    /// Collects sent messages instead of sending them, for a [testing::TestBot]
+    #[cfg(any(test, feature = "testing"))]
+    outbox: Option<std::sync::Arc<record::Outbox>>,
+    recorder: Option<std::sync::Arc<record::Recorder>>,
+    casemapping: std::sync::Mutex<util::CaseMapping>,
 }
 
 impl Bot {
@@ -61,39 +116,140 @@ impl Bot {
     /// Loads configuration from `CATINATOR_` environment variables and the `config.toml` file
     /// Starts the connection to the irc server.
     pub async fn new() -> Result<Bot> {
-        let figment = config::Config::figment();
+        Bot::with_figment(config::Config::figment()).await
+    }
+
+    /// Initializes the bot with the config extracted from `figment` instead of the
+    /// default sources, see [Config::figment](config::Config::figment).
+    /// Starts the connection to the irc server.
+    pub async fn with_figment(figment: figment::Figment) -> Result<Bot> {
         let config: config::Config = figment.extract().context("failed to extract config")?;
 
         let irc_client = Client::from_config(config.clone().into()).await?;
-
-        let bot = Bot {
-            irc_client,
-            config,
-            figment,
-        };
+        let bot = Bot::with_client(figment, config, irc_client)?;
 
         if bot.config.server.sasl && bot.config.user.password.is_some() {
             tracing::info!("initializing sasl");
             bot.sasl_init().unwrap()
-        } else if let Some(password) = bot.config.server.password.as_ref() {
-            tracing::info!("sending server password");
-            bot.irc_client.send(Command::PASS(password.clone()))?;
+        } else {
+            if let Some(password) = bot.config.server.password.as_ref() {
+                tracing::info!("sending server password");
+                bot.send(Command::PASS(password.clone()))?;
+            }
+            bot.request_capabilities()?;
             bot.register_connection()?;
+            bot.send(Command::CAP(
+                None,
+                irc_proto::command::CapSubCommand::END,
+                None,
+                None,
+            ))?;
         }
 
         Ok(bot)
     }
 
+    /// Create a bot with the config extracted from `figment` that does not connect,
+    /// for the [console], [replays](record) and tests. Nothing is recorded.
+    pub(crate) async fn offline(figment: figment::Figment) -> Result<Bot> {
+        let mut config: config::Config = figment.extract().context("failed to extract config")?;
+        config.settings.record = None;
+
+        let mut irc_config: Config = config.clone().into();
+        irc_config.use_mock_connection = true;
+        let irc_client = Client::from_config(irc_config).await?;
+
+        Bot::with_client(figment, config, irc_client)
+    }
+
+    /// Create the bot around an `irc_client` that is not registered yet
+    fn with_client(
+        figment: figment::Figment,
+        config: config::Config,
+        irc_client: irc::client::Client,
+    ) -> Result<Bot> {
+        let events = events::Events::new(config.settings.event_capacity);
+
+        let state = state::State::load(config.settings.state_file.clone())
+            .context("failed to load state")?;
+
+        let recorder = match &config.settings.record {
+            Some(path) => Some(std::sync::Arc::new(record::Recorder::open(path)?)),
+            None => None,
+        };
+
+        Ok(Bot {
+            irc_client,
+            config,
+            figment,
+            state,
+            handlers: Vec::new(),
+            cooldowns: cooldown::Cooldowns::new(),
+            events,
+            middleware: Vec::new(),
+            panics: Default::default(),
+            incoming: Default::default(),
+            #[cfg(any(test, feature = "testing"))]
+            outbox: None,
+            recorder,
+            casemapping: Default::default(),
+        })
+    }
+
+    /// Stream of incoming messages read by the event loop of the [catinator] macro,
+    /// these come from the server unless the bot runs in the [console] or [replays](record).
+    pub fn stream(
+        &mut self,
+    ) -> Result<BoxStream<'static, std::result::Result<Message, irc::error::Error>>> {
+        let incoming = self
+            .incoming
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+
+        let stream = match incoming {
+            Some(incoming) => incoming.map(Ok).boxed(),
+            None => self.irc_client.stream()?.boxed(),
+        };
+
+        match self.recorder.clone() {
+            Some(recorder) => Ok(stream
+                .inspect(move |msg| {
+                    if let Ok(msg) = msg {
+                        recorder.record(record::Direction::In, msg)
+                    }
+                })
+                .boxed()),
+            None => Ok(stream),
+        }
+    }
+
     /// Get the bots figment to use when building your own configuration.
     /// See [config]
     pub fn figment(&self) -> &figment::Figment {
         &self.figment
     }
 
+    /// Extract the config of the plugin `name` from its `[plugins.<name>]` table, see
+    /// [config]. Missing fields use their serde defaults and an empty table is used if
+    /// there is none.
+    pub fn plugin_config<T: serde::de::DeserializeOwned>(&self, name: &str) -> Result<T> {
+        self.figment
+            .focus(&format!("plugins.{}", name))
+            .extract()
+            .with_context(|| {
+                format!(
+                    "invalid config for plugin {}, set it in [plugins.{}] or with CATINATOR_PLUGINS__{}__<KEY>",
+                    name,
+                    name,
+                    name.to_ascii_uppercase()
+                )
+            })
+    }
+
     pub fn register_connection(&self) -> Result<()> {
-        self.irc_client
-            .send(Command::NICK(self.config.user.nickname.clone()))?;
-        self.irc_client.send(Command::USER(
+        self.send(Command::NICK(self.config.user.nickname.clone()))?;
+        self.send(Command::USER(
             self.config.user.username.clone(),
             "0".to_owned(),
             self.config.user.realname.clone(),
@@ -102,24 +258,227 @@ impl Bot {
         Ok(())
     }
 
+    /// Request the capabilities used to resolve [permissions](permission).
+    ///
+    /// They are requested one by one as the server rejects the whole request
+    /// if a single capability is not supported.
+    pub fn request_capabilities(&self) -> Result<()> {
+        use irc::client::prelude::Capability;
+
+        for capability in [Capability::AccountTag, Capability::MultiPrefix] {
+            self.request_capability(capability)?;
+        }
+
+        Ok(())
+    }
+
+    fn request_capability(&self, capability: Capability) -> Result<()> {
+        self.send(Command::CAP(
+            None,
+            irc_proto::command::CapSubCommand::REQ,
+            None,
+            Some(capability.as_ref().to_string()),
+        ))?;
+
+        Ok(())
+    }
+
     /// Initialize a sasl connection, you usually don't need
     /// to run this yourself as it is done during [Bot::new].
     pub fn sasl_init(&self) -> Result<()> {
-        self.irc_client
-            .send_cap_req(&[irc::client::prelude::Capability::Sasl])?;
+        self.request_capability(Capability::Sasl)?;
+        self.request_capabilities()?;
         self.register_connection()?;
-        self.irc_client.send_sasl_plain()?;
+        self.send(Command::AUTHENTICATE("PLAIN".to_string()))?;
 
         Ok(())
     }
 
+    /// Resolve the [permission level](permission::Level) of the sender of `msg`
+    pub fn permission(&self, msg: &Message) -> permission::Level {
+        self.permission_in(msg, util::channel(msg))
+    }
+
+    /// Resolve the [permission level](permission::Level) the sender of `msg` has in `channel`,
+    /// which can differ from the one the message was sent to.
+    pub fn permission_in(&self, msg: &Message, channel: Option<&str>) -> permission::Level {
+        use permission::{entry_matches, Level};
+
+        let settings = &self.config.settings;
+
+        if settings
+            .owners
+            .iter()
+            .any(|entry| entry_matches(entry, msg))
+        {
+            return Level::Owner;
+        }
+
+        if settings
+            .admins
+            .iter()
+            .any(|entry| entry_matches(entry, msg))
+        {
+            return Level::Admin;
+        }
+
+        let channel_level = match (channel, msg.source_nickname()) {
+            (Some(channel), Some(nick)) => self
+                .irc_client
+                .list_users(channel)
+                .map(|users| permission::channel_level(&users, nick, self.casemapping()))
+                .unwrap_or_default(),
+            _ => Level::Anyone,
+        };
+
+        if channel_level == Level::Anyone && permission::account(msg).is_some() {
+            Level::Identified
+        } else {
+            channel_level
+        }
+    }
+
+    /// Check if the sender of `msg` has at least the permission `level` required for `name`.
+    ///
+    /// Denied users get a notice and the attempt is logged.
+    pub fn check_permission(&self, msg: &Message, name: &str, level: permission::Level) -> bool {
+        if level == permission::Level::Anyone {
+            return true;
+        }
+
+        let actual = self.permission(msg);
+        if actual >= level {
+            return true;
+        }
+
+        tracing::info!(
+            target: "permission",
+            "denied {} for {:?} with level {}, requires {}",
+            name,
+            msg.prefix,
+            actual,
+            level
+        );
+
+        if let Some(nick) = msg.source_nickname() {
+            let _ = self.send_notice(nick, &format!("{} requires {} permissions", name, level));
+        }
+
+        false
+    }
+
+    /// Check if the sender of `msg` is on the ignore list of the config or
+    /// added at runtime. Admins and owners are never ignored.
+    pub fn is_ignored(&self, msg: &Message) -> bool {
+        if msg.source_nickname().is_none() {
+            return false;
+        }
+
+        let ignored = self
+            .config
+            .settings
+            .ignore
+            .iter()
+            .cloned()
+            .chain(self.state.ignored())
+            .any(|entry| permission::ignore_matches(&entry, msg));
+
+        if ignored && self.permission(msg) < permission::Level::Admin {
+            debug!(target: "ignore", "ignoring {:?}", msg.prefix);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check if the handler `name` is enabled for the channel `msg` was sent to.
+    /// Messages that are not related to a channel are always enabled.
+    pub fn is_enabled(&self, msg: &Message, name: &str) -> bool {
+        if self.is_faulted(name) {
+            return false;
+        }
+
+        match util::channel(msg) {
+            Some(channel) => self.state.is_enabled(channel, name, self.casemapping()),
+            None => true,
+        }
+    }
+
+    /// Check if the command `name` is off cooldown for the sender of `msg` and start it if so.
+    ///
+    /// The `user` and `channel` cooldowns are the defaults set in the [catinator] macro,
+    /// the [`cooldowns`](config::Settings::cooldowns) setting overrides each of them it sets.
+    /// Admins are exempt from cooldowns.
+    pub fn check_cooldown(
+        &self,
+        msg: &Message,
+        name: &str,
+        user: Option<std::time::Duration>,
+        channel: Option<std::time::Duration>,
+    ) -> bool {
+        let (user, channel) = match self.config.settings.cooldowns.get(name) {
+            Some(cooldown) => (cooldown.user.or(user), cooldown.channel.or(channel)),
+            None => (user, channel),
+        };
+
+        if user.is_none() && channel.is_none() {
+            return true;
+        }
+
+        let nick = match msg.source_nickname() {
+            Some(nick) => nick,
+            None => return true,
+        };
+
+        if self.permission(msg) >= permission::Level::Admin {
+            return true;
+        }
+
+        match self.cooldowns.check(
+            name,
+            nick,
+            util::channel(msg),
+            user,
+            channel,
+            std::time::Instant::now(),
+        ) {
+            Ok(()) => true,
+            Err(active) => {
+                debug!(target: "cooldown", "{} is on cooldown for {}", name, nick);
+
+                if self.config.settings.cooldown_notice && !active.notified {
+                    let _ = self.send_notice(
+                        nick,
+                        &format!(
+                            "slow down, you can use {} again in {}",
+                            name,
+                            util::format_duration(std::time::Duration::from_secs(
+                                active.remaining.as_millis().div_ceil(1000) as u64
+                            ))
+                        ),
+                    );
+                }
+
+                false
+            }
+        }
+    }
+
+    /// Emit an event to the subscribers of its type, see [events]
+    pub fn emit<T: std::any::Any + Send>(&self, event: T) -> Result<()> {
+        self.events.emit(event)
+    }
+
     /// Send a privmsg to the target `#channel` or `user`
     pub fn send_privmsg(
         &self,
         target: &str,
         message: &str,
     ) -> std::result::Result<(), irc::error::Error> {
-        self.irc_client.send_privmsg(target, message)
+        for line in message.split("\r\n") {
+            self.send(Command::PRIVMSG(target.to_string(), line.to_string()))?
+        }
+        Ok(())
     }
 
     /// Send a notice to the target `#channel` or `user`
@@ -128,7 +487,10 @@ impl Bot {
         target: &str,
         message: &str,
     ) -> std::result::Result<(), irc::error::Error> {
-        self.irc_client.send_notice(target, message)
+        for line in message.split("\r\n") {
+            self.send(Command::NOTICE(target.to_string(), line.to_string()))?
+        }
+        Ok(())
     }
 
     /// Send an action (`/me`) to the target `#channel` or `user`
@@ -137,6 +499,12 @@ impl Bot {
         target: &str,
         message: &str,
     ) -> std::result::Result<(), irc::error::Error> {
-        self.irc_client.send_action(target, message)
+        for line in message.split("\r\n") {
+            self.send(Command::PRIVMSG(
+                target.to_string(),
+                format!("\u{001}ACTION {}\u{001}", line),
+            ))?
+        }
+        Ok(())
     }
 }
@@ -1,20 +1,119 @@
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+const USAGE: &str =
+    "usage: catinator [--console [--nick <nick>] [--channel <#channel>]] [--replay <file>]
+       catinator check-config [--profile <profile>]
+
+    check-config   validate the config and print where each value comes from
+    --profile      profile to check (default: debug or release, like the build)
+    --console      read messages from stdin and print replies instead of connecting
+    --replay       replay a recording and diff the replies instead of connecting
+    --nick         nick the console messages are sent by (default: console)
+    --channel      channel the console messages are sent to (default: #console)";
+
+/// Command line arguments
+struct Args {
+    check_config: bool,
+    profile: String,
+    console: bool,
+    replay: Option<std::path::PathBuf>,
+    nick: String,
+    channel: String,
+}
+
+impl Args {
+    fn parse() -> Result<Args, String> {
+        let mut args = Args {
+            check_config: false,
+            profile: catinator::config::Config::PROFILE.to_string(),
+            console: false,
+            replay: None,
+            nick: "console".to_string(),
+            channel: "#console".to_string(),
+        };
+
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "check-config" => args.check_config = true,
+                "--profile" => args.profile = iter.next().ok_or("--profile requires a value")?,
+                "--console" => args.console = true,
+                "--replay" => {
+                    args.replay = Some(iter.next().ok_or("--replay requires a file")?.into())
+                }
+                "--nick" => args.nick = iter.next().ok_or("--nick requires a value")?,
+                "--channel" => args.channel = iter.next().ok_or("--channel requires a value")?,
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                arg => return Err(format!("unknown argument {}\n\n{}", arg, USAGE)),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
 #[tokio::main]
 async fn main() {
     use catinator::catinator;
 
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2)
+    });
+
+    // keep stdout free for the replies in the console and replay
+    let writer = if args.check_config || args.console || args.replay.is_some() {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+
+    // show warnings like world readable secret files when checking the config
+    let filter = if args.check_config {
+        tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn"))
+    } else {
+        tracing_subscriber::EnvFilter::from_default_env()
+    };
+
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::TRACE)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_env_filter(filter)
+        .with_writer(writer)
         .init();
 
+    if args.check_config {
+        let valid = catinator::check::check_config(&args.profile);
+        std::process::exit(if valid { 0 } else { 1 })
+    }
+
     rustls::crypto::CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider())
         .unwrap();
 
-    let mut bot = catinator::Bot::new().await.unwrap();
+    let offline = args.console || args.replay.is_some();
+    let mut replay = None;
+    let mut bot = if let Some(path) = &args.replay {
+        let (bot, recording) = catinator::Bot::replay(path).await.unwrap();
+        replay = Some(recording);
+        bot
+    } else if args.console {
+        catinator::Bot::console(&args.nick, &args.channel)
+            .await
+            .unwrap()
+    } else {
+        catinator::Bot::new().await.unwrap()
+    };
 
     let mut sed = catinator::hooks::sed::Sed::new();
-    let wolfram_alpha = catinator::hooks::wolfram_alpha::WolframAlpha::new(&bot)
-        .expect("failed to initialize WolframAlpha command");
+    let wolfram_alpha = match catinator::hooks::wolfram_alpha::WolframAlpha::new(&bot) {
+        Ok(wolfram_alpha) => wolfram_alpha,
+        // the console and replay should work without every plugin configured
+        Err(err) if offline => {
+            tracing::warn!("wolfram alpha is not configured: {:?}", err);
+            catinator::hooks::wolfram_alpha::WolframAlpha::unconfigured()
+        }
+        Err(err) => panic!("failed to initialize WolframAlpha command: {:?}", err),
+    };
 
     catinator![
         // hook(
@@ -27,12 +126,13 @@ async fn main() {
             "sed_log",
             "Log messages for use with sed replace, max 10k lines.",
             PRIVMSG,
-            sed.log
+            sed.log,
+            ignored = true
         ),
         matcher(
             "nitter",
             "replace twitter urls with a nitter instance (xcancel.com)",
-            r"https:\/\/(twitter|x)\.com\/",
+            r"https://(?:twitter|x)\.com/(\S+)",
             catinator::hooks::nitter
         ),
         matcher(
@@ -56,7 +156,8 @@ async fn main() {
         command(
             "pet",
             "Pet the cat, cats generally like pets.",
-            catinator::hooks::pet
+            catinator::hooks::pet,
+            channel_cooldown = "5s"
         ),
         command(
             "about",
@@ -66,12 +167,20 @@ async fn main() {
         async command(
             "wa",
             "Returns Wolfram Alpha results for a query",
-            wolfram_alpha.wa
+            wolfram_alpha.wa,
+            cooldown = "30s"
         ),
         async command(
             "hal",
             "Returns Wolfram Alpha results in natural language",
-            wolfram_alpha.hal
+            wolfram_alpha.hal,
+            cooldown = "30s"
         ),
     ];
+
+    if let Some(replay) = replay {
+        if !replay.report() {
+            std::process::exit(1)
+        }
+    }
 }
@@ -0,0 +1,181 @@
+//! Middleware adds behavior around the dispatch of all handlers, like metrics,
+//! output censoring or a dry-run mode, without changing the handlers themselves.
+//!
+//! A [Middleware] can veto incoming messages before any handler sees them,
+//! modify or drop everything the bot sends through [Bot::send] and its helpers,
+//! and inspect or replace the results of handlers.
+//!
+//! Middleware runs in the order it was added with [Bot::add_middleware], the
+//! [`middleware`](crate::config::Settings::middleware) setting can change that order by name.
+//!
+//! ```
+//! use irc::client::prelude::*;
+//! use catinator::{middleware::Middleware, Bot};
+//!
+//! /// Log replies instead of sending them
+//! struct DryRun;
+//!
+//! impl Middleware for DryRun {
+//!     fn name(&self) -> &str {
+//!         "dry_run"
+//!     }
+//!
+//!     fn outgoing(&self, _bot: &Bot, msg: Message) -> Option<Message> {
+//!         tracing::info!("would send: {}", msg);
+//!         None
+//!     }
+//! }
+//!
+//! # fn add(bot: &mut Bot) {
+//! bot.add_middleware(DryRun);
+//! # }
+//! ```
+
+use irc::client::prelude::*;
+
+use crate::{Bot, Kind};
+
+/// Behavior around the dispatch of handlers, every method has a default that does nothing
+pub trait Middleware: Send + Sync {
+    /// Name used to order middleware in the config
+    fn name(&self) -> &str;
+
+    /// Inspect an incoming message before any handler runs, return false to drop it
+    fn incoming(&self, _bot: &Bot, _msg: &Message) -> bool {
+        true
+    }
+
+    /// Inspect or modify a message the bot is about to send, return `None` to drop it.
+    ///
+    /// Sending from here runs the middleware again, so be careful not to loop.
+    fn outgoing(&self, _bot: &Bot, msg: Message) -> Option<Message> {
+        Some(msg)
+    }
+
+    /// Inspect or replace the result of the handler `name` after it ran
+    fn result(
+        &self,
+        _bot: &Bot,
+        _kind: Kind,
+        _name: &str,
+        result: anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        result
+    }
+}
+
+/// Position of the middleware `name` in the configured `order`, unlisted ones go last
+fn position(order: &[String], name: &str) -> usize {
+    order
+        .iter()
+        .position(|entry| entry == name)
+        .unwrap_or(usize::MAX)
+}
+
+/// Index to insert the middleware `name` at into the `chain` of names,
+/// after everything that comes before it or is in the same position in `order`
+fn insert_index<'a>(order: &[String], chain: impl Iterator<Item = &'a str>, name: &str) -> usize {
+    let pos = position(order, name);
+    let mut len = 0;
+
+    for (index, other) in chain.enumerate() {
+        if position(order, other) > pos {
+            return index;
+        }
+        len = index + 1;
+    }
+
+    len
+}
+
+impl Bot {
+    /// Add a middleware to the chain, see the [module documentation](self)
+    pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
+        let index = insert_index(
+            &self.config.settings.middleware,
+            self.middleware.iter().map(|other| other.name()),
+            middleware.name(),
+        );
+
+        tracing::debug!("adding middleware {} at {}", middleware.name(), index);
+        self.middleware.insert(index, Box::new(middleware));
+    }
+
+    /// Sort the chain again after the [`middleware`](crate::config::Settings::middleware)
+    /// setting was reloaded, keeping the order of the ones in the same position
+    pub(crate) fn reorder_middleware(&mut self) {
+        let order = &self.config.settings.middleware;
+        self.middleware
+            .sort_by_key(|middleware| position(order, middleware.name()));
+    }
+
+    /// Run the incoming middleware for `msg`, returns false if it should be dropped
+    pub fn incoming(&self, msg: &Message) -> bool {
+        self.middleware.iter().all(|middleware| {
+            let pass = middleware.incoming(self, msg);
+            if !pass {
+                tracing::debug!(target: "middleware", "{} dropped {:?}", middleware.name(), msg);
+            }
+            pass
+        })
+    }
+
+    /// Run the outgoing middleware for `msg`, returns `None` if it should not be sent
+    pub fn outgoing(&self, msg: Message) -> Option<Message> {
+        self.middleware
+            .iter()
+            .try_fold(msg, |msg, middleware| middleware.outgoing(self, msg))
+    }
+
+    /// Run the result middleware for the `result` of the handler `name`
+    pub fn handled(
+        &self,
+        kind: Kind,
+        name: &str,
+        result: anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        self.middleware.iter().fold(result, |result, middleware| {
+            middleware.result(self, kind, name, result)
+        })
+    }
+
+    /// Send a message to the server, passing it through the outgoing middleware
+    /// and [recording](crate::record) it if enabled
+    pub fn send<M: Into<Message>>(&self, msg: M) -> Result<(), irc::error::Error> {
+        let msg = match self.outgoing(msg.into()) {
+            Some(msg) => msg,
+            None => return Ok(()),
+        };
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(crate::record::Direction::Out, &msg);
+        }
+
+        #[cfg(any(test, feature = "testing"))]
+        if let Some(outbox) = &self.outbox {
+            outbox.push(msg);
+            return Ok(());
+        }
+
+        self.irc_client.send(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_index() {
+        let order = ["censor".to_string(), "metrics".to_string()];
+
+        let mut chain: Vec<&str> = Vec::new();
+        for name in ["dry_run", "metrics", "log", "censor"] {
+            let index = insert_index(&order, chain.iter().copied(), name);
+            chain.insert(index, name);
+        }
+
+        // configured ones first, the rest in the order they were added
+        assert_eq!(chain, ["censor", "metrics", "dry_run", "log"]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Permission levels required to use commands.
+//!
+//! The level of a user is resolved from the configured
+//! [`owners`](crate::config::Settings::owners) and [`admins`](crate::config::Settings::admins),
+//! their modes in the channel the message was sent to and whether they are identified
+//! with services.
+//!
+//! Entries in `owners` and `admins` are either hostmasks like `*!*@cocaine.farm`
+//! or services account names prefixed with `$a:` like `$a:audron`.
+//! Accounts are resolved with the [account-tag](https://ircv3.net/specs/extensions/account-tag)
+//! capability, `*` and `?` wildcards are supported for both.
+//!
+//! The same entries are used for the [`ignore`](crate::config::Settings::ignore) list,
+//! which additionally accepts plain nicks like `relaybot`.
+
+use irc::client::data::AccessLevel;
+use irc::client::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::util;
+
+/// Permission level of a user, ordered from least to most privileged
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Everyone
+    #[default]
+    Anyone,
+    /// Users that are logged in with services
+    Identified,
+    /// Users with voice (+v) in the channel
+    Voiced,
+    /// Channel operators (+h, +o, +a, +q)
+    Op,
+    /// Users matching the configured `admins`
+    Admin,
+    /// Users matching the configured `owners`
+    Owner,
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Level::Anyone => "anyone",
+            Level::Identified => "identified",
+            Level::Voiced => "voiced",
+            Level::Op => "op",
+            Level::Admin => "admin",
+            Level::Owner => "owner",
+        })
+    }
+}
+
+/// Get the services account of the messages sender from the `account` tag
+pub fn account(msg: &Message) -> Option<&str> {
+    msg.tags
+        .as_ref()?
+        .iter()
+        .find(|tag| tag.0 == "account")
+        .and_then(|tag| tag.1.as_deref())
+}
+
+/// Check if `entry` from the `owners` or `admins` list matches the sender of `msg`
+pub fn entry_matches(entry: &str, msg: &Message) -> bool {
+    match entry.strip_prefix("$a:") {
+        Some(pattern) => account(msg).is_some_and(|account| util::mask_matches(pattern, account)),
+        None => util::hostmask(msg).is_some_and(|hostmask| util::mask_matches(entry, &hostmask)),
+    }
+}
+
+/// Check if the ignore list `entry` matches the sender of `msg`.
+///
+/// Entries without `!` or `@` that are not accounts match the nick only.
+pub fn ignore_matches(entry: &str, msg: &Message) -> bool {
+    if entry.starts_with("$a:") || entry.contains(['!', '@']) {
+        entry_matches(entry, msg)
+    } else {
+        msg.source_nickname()
+            .is_some_and(|nick| util::mask_matches(entry, nick))
+    }
+}
+
+/// Get the level `nick` has through their modes in `users`,
+/// nicks are compared using the servers `casemapping`
+pub(crate) fn channel_level(
+    users: &[irc::client::data::User],
+    nick: &str,
+    casemapping: util::CaseMapping,
+) -> Level {
+    let user = users
+        .iter()
+        .find(|user| casemapping.eq(user.get_nickname(), nick));
+
+    match user.map(|user| user.highest_access_level()) {
+        Some(AccessLevel::Owner | AccessLevel::Admin | AccessLevel::Oper | AccessLevel::HalfOp) => {
+            Level::Op
+        }
+        Some(AccessLevel::Voice) => Level::Voiced,
+        _ => Level::Anyone,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(tags: &str) -> Message {
+        format!("{}:audron!audron@cocaine.farm PRIVMSG #chan :hi\r\n", tags)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_entry_matches_hostmask() {
+        let msg = message("");
+        assert!(entry_matches("*!*@cocaine.farm", &msg));
+        assert!(!entry_matches("*!*@example.com", &msg));
+    }
+
+    #[test]
+    fn test_entry_matches_account() {
+        let msg = message("@account=Audron ");
+        assert_eq!(account(&msg), Some("Audron"));
+        assert!(entry_matches("$a:audron", &msg));
+        assert!(!entry_matches("$a:basso", &msg));
+
+        assert!(!entry_matches("$a:audron", &message("")));
+    }
+
+    #[test]
+    fn test_ignore_matches() {
+        let msg = message("@account=relay ");
+        assert!(ignore_matches("Audron", &msg));
+        assert!(ignore_matches("aud*", &msg));
+        assert!(ignore_matches("*!*@cocaine.farm", &msg));
+        assert!(ignore_matches("$a:relay", &msg));
+        assert!(!ignore_matches("basso", &msg));
+        assert!(!ignore_matches("audron@example.com", &msg));
+    }
+
+    #[test]
+    fn test_channel_level() {
+        use irc::client::data::User;
+
+        let rfc1459 = util::CaseMapping::Rfc1459;
+        let users = vec![
+            User::new("@audron"),
+            User::new("+basso"),
+            User::new("mett"),
+            User::new("@cat[]"),
+        ];
+        assert_eq!(channel_level(&users, "Audron", rfc1459), Level::Op);
+        assert_eq!(channel_level(&users, "basso", rfc1459), Level::Voiced);
+        assert_eq!(channel_level(&users, "mett", rfc1459), Level::Anyone);
+        assert_eq!(channel_level(&users, "nobody", rfc1459), Level::Anyone);
+
+        // nicks differing in the rfc1459 special characters are the same
+        assert_eq!(channel_level(&users, "CAT{}", rfc1459), Level::Op);
+        assert_eq!(
+            channel_level(&users, "CAT{}", util::CaseMapping::Ascii),
+            Level::Anyone
+        );
+    }
+
+    #[test]
+    fn test_level_order() {
+        assert!(Level::Owner > Level::Admin);
+        assert!(Level::Admin > Level::Op);
+        assert!(Level::Op > Level::Voiced);
+        assert!(Level::Voiced > Level::Identified);
+        assert!(Level::Identified > Level::Anyone);
+    }
+}
@@ -0,0 +1,491 @@
+//! Record irc traffic to reproduce problems offline.
+//!
+//! With [`record`](crate::config::Settings::record) set every line received from or
+//! sent to the server is appended to that file as a JSON line with a timestamp:
+//!
+//! ```text
+//! {"time":"2024-05-06T09:00:00.000Z","direction":"in","line":":audron!a@b PRIVMSG #cats ::pet"}
+//! {"time":"2024-05-06T09:00:00.002Z","direction":"out","line":"PRIVMSG #cats :\u0001ACTION purrs\u0001"}
+//! ```
+//!
+//! Server and operator passwords, SASL payloads and messages to NickServ are redacted.
+//!
+//! A recording can be replayed with `catinator --replay <file>`, which feeds the
+//! received lines through the handlers without a connection and prints a diff of
+//! the bots replies against the recorded ones. Connection setup like `CAP`, `NICK`
+//! or `AUTHENTICATE` is not compared.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{Context as _, Result};
+use futures::stream::{self, StreamExt};
+use irc::client::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{middleware::Middleware, Bot};
+
+/// Replaces redacted parts of a line
+const REDACTED: &str = "<redacted>";
+
+/// Whether a line was received or sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// A recorded line
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Entry {
+    /// RFC 3339 time the line was received or sent
+    pub time: String,
+    pub direction: Direction,
+    /// The raw line without the trailing newline
+    pub line: String,
+}
+
+/// Redacts passwords and SASL payloads, following SASL exchanges across lines
+#[derive(Debug, Default)]
+struct Redactor {
+    /// A mechanism was sent and the exchange did not end yet,
+    /// every AUTHENTICATE is a payload until then
+    authenticating: bool,
+}
+
+impl Redactor {
+    /// Redact passwords and SASL payloads in `msg`
+    fn redact(&mut self, msg: &Message) -> Message {
+        let command = match &msg.command {
+            Command::PASS(_) => Command::PASS(REDACTED.to_string()),
+            Command::OPER(name, _) => Command::OPER(name.clone(), REDACTED.to_string()),
+            // `+` (empty) and `*` (abort) are not secret
+            Command::AUTHENTICATE(data) if data == "+" || data == "*" => msg.command.clone(),
+            // the first AUTHENTICATE of an exchange names the mechanism
+            Command::AUTHENTICATE(data) if !self.authenticating && is_mechanism(data) => {
+                self.authenticating = true;
+                msg.command.clone()
+            }
+            Command::AUTHENTICATE(_) => Command::AUTHENTICATE(REDACTED.to_string()),
+            Command::Response(
+                Response::RPL_SASLSUCCESS
+                | Response::ERR_SASLFAIL
+                | Response::ERR_NICKLOCKED
+                | Response::ERR_SASLTOOLONG
+                | Response::ERR_SASLABORT
+                | Response::ERR_SASLALREADY,
+                _,
+            ) => {
+                self.authenticating = false;
+                msg.command.clone()
+            }
+            Command::NICKSERV(args) if !args.is_empty() => Command::NICKSERV(
+                std::iter::once(args[0].clone())
+                    .chain(args[1..].iter().map(|_| REDACTED.to_string()))
+                    .collect(),
+            ),
+            Command::PRIVMSG(target, _) if target.eq_ignore_ascii_case("nickserv") => {
+                Command::PRIVMSG(target.clone(), REDACTED.to_string())
+            }
+            command => command.clone(),
+        };
+
+        Message {
+            tags: msg.tags.clone(),
+            prefix: msg.prefix.clone(),
+            command,
+        }
+    }
+}
+
+/// Check if `data` is the name of a SASL mechanism like `PLAIN` or `SCRAM-SHA-256`
+fn is_mechanism(data: &str) -> bool {
+    [
+        "PLAIN",
+        "EXTERNAL",
+        "ECDSA-NIST256P-CHALLENGE",
+        "SCRAM-SHA-1",
+        "SCRAM-SHA-256",
+        "SCRAM-SHA-512",
+    ]
+    .contains(&data)
+}
+
+/// Messages sent by a bot that does not connect, for replays and tests
+#[derive(Debug, Default)]
+pub(crate) struct Outbox(Mutex<Vec<Message>>);
+
+impl Outbox {
+    fn messages(&self) -> MutexGuard<'_, Vec<Message>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn push(&self, msg: Message) {
+        self.messages().push(msg)
+    }
+
+    pub(crate) fn sent(&self) -> Vec<Message> {
+        self.messages().clone()
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn take(&self) -> Vec<Message> {
+        std::mem::take(&mut *self.messages())
+    }
+}
+
+/// Collects outgoing messages into an [Outbox] instead of sending them
+struct Capture(Arc<Outbox>);
+
+impl Middleware for Capture {
+    fn name(&self) -> &str {
+        "replay"
+    }
+
+    fn outgoing(&self, _bot: &Bot, msg: Message) -> Option<Message> {
+        self.0.push(msg);
+        None
+    }
+}
+
+/// Appends the traffic of a bot to a file
+#[derive(Debug)]
+pub(crate) struct Recorder {
+    file: Mutex<File>,
+    redactor: Mutex<Redactor>,
+}
+
+impl Recorder {
+    pub(crate) fn open(path: &Path) -> Result<Recorder> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open recording {}", path.display()))?;
+
+        tracing::info!("recording irc traffic to {}", path.display());
+        Ok(Recorder {
+            file: Mutex::new(file),
+            redactor: Default::default(),
+        })
+    }
+
+    /// Append `msg`, errors are only logged to not break the bot
+    pub(crate) fn record(&self, direction: Direction, msg: &Message) {
+        let msg = self
+            .redactor
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .redact(msg);
+
+        let entry = Entry {
+            time: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            direction,
+            line: msg.to_string().trim_end().to_string(),
+        };
+
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+                Ok(writeln!(file, "{}", json)?)
+            });
+
+        if let Err(err) = result {
+            tracing::warn!("failed to record {:?}: {:?}", entry.line, err);
+        }
+    }
+}
+
+/// Load the recording at `path`
+pub fn load(path: &Path) -> Result<Vec<Entry>> {
+    let file =
+        File::open(path).with_context(|| format!("failed to open recording {}", path.display()))?;
+
+    BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|(index, line)| {
+            serde_json::from_str(&line?).with_context(|| {
+                format!("invalid entry on line {} of {}", index + 1, path.display())
+            })
+        })
+        .collect()
+}
+
+/// Whether replies with this line are compared, connection setup is not
+fn compared(line: &str) -> bool {
+    let command = line.split_whitespace().next().unwrap_or_default();
+    ![
+        "CAP",
+        "NICK",
+        "USER",
+        "PASS",
+        "AUTHENTICATE",
+        "PING",
+        "PONG",
+    ]
+    .contains(&command)
+}
+
+/// A line of the difference between the recorded and replayed replies
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Sent in the recording and the replay
+    Same(String),
+    /// Only sent in the recording
+    Missing(String),
+    /// Only sent in the replay
+    Added(String),
+}
+
+/// Diff the `expected` against the `actual` lines using their longest common subsequence
+fn diff(expected: &[String], actual: &[String]) -> Vec<Change> {
+    let (n, m) = (expected.len(), actual.len());
+
+    // lcs[i][j] is the length of the lcs of expected[i..] and actual[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            changes.push(Change::Same(expected[i].clone()));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            changes.push(Change::Added(actual[j].clone()));
+            j += 1;
+        } else {
+            changes.push(Change::Missing(expected[i].clone()));
+            i += 1;
+        }
+    }
+
+    changes
+}
+
+/// A recording being replayed, see the [module documentation](self)
+pub struct Replay {
+    expected: Vec<String>,
+    outbox: Arc<Outbox>,
+}
+
+impl Replay {
+    /// Diff the recorded replies against the ones sent during the replay so far
+    pub fn diff(&self) -> Vec<Change> {
+        let mut redactor = Redactor::default();
+        let actual: Vec<String> = self
+            .outbox
+            .sent()
+            .iter()
+            .map(|msg| redactor.redact(msg).to_string().trim_end().to_string())
+            .filter(|line| compared(line))
+            .collect();
+
+        diff(&self.expected, &actual)
+    }
+
+    /// Print the differences, returns false if there were any
+    pub fn report(&self) -> bool {
+        let changes = self.diff();
+        let mut same = true;
+
+        for change in &changes {
+            match change {
+                Change::Same(_) => {}
+                Change::Missing(line) => {
+                    same = false;
+                    println!("- {}", line)
+                }
+                Change::Added(line) => {
+                    same = false;
+                    println!("+ {}", line)
+                }
+            }
+        }
+
+        if same {
+            println!("replay matches the recording, {} replies", changes.len());
+        }
+
+        same
+    }
+}
+
+impl Bot {
+    /// Initializes the bot to [replay](self) the recording at `path`, the received lines
+    /// are read by the event loop instead of the server. The config is loaded like in
+    /// [Bot::new] but no connection is made and nothing is recorded.
+    pub async fn replay(path: &Path) -> Result<(Bot, Replay)> {
+        let entries = load(path)?;
+
+        let mut bot = Bot::offline(crate::config::Config::figment()).await?;
+        let outbox = Arc::new(Outbox::default());
+        bot.add_middleware(Capture(outbox.clone()));
+
+        let mut incoming = Vec::new();
+        let mut expected = Vec::new();
+        for entry in entries {
+            match entry.direction {
+                Direction::In => match entry.line.parse::<Message>() {
+                    Ok(msg) => incoming.push(msg),
+                    Err(err) => tracing::warn!("skipping {:?}: {}", entry.line, err),
+                },
+                Direction::Out if compared(&entry.line) => expected.push(entry.line),
+                Direction::Out => {}
+            }
+        }
+
+        tracing::info!(
+            "replaying {} messages from {}",
+            incoming.len(),
+            path.display()
+        );
+        *bot.incoming
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner()) = Some(stream::iter(incoming).boxed());
+
+        Ok((bot, Replay { expected, outbox }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(command: Command) -> Message {
+        Message {
+            tags: None,
+            prefix: None,
+            command,
+        }
+    }
+
+    fn line(msg: &Message) -> String {
+        Redactor::default()
+            .redact(msg)
+            .to_string()
+            .trim_end()
+            .to_string()
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            line(&msg(Command::PASS("hunter2".into()))),
+            "PASS <redacted>"
+        );
+        assert_eq!(
+            line(&msg(Command::AUTHENTICATE(
+                "AGNhdGluYXRvcgBodW50ZXIyMg==".into()
+            ))),
+            "AUTHENTICATE <redacted>"
+        );
+        assert_eq!(
+            line(&msg(Command::AUTHENTICATE("PLAIN".into()))),
+            "AUTHENTICATE PLAIN"
+        );
+        assert_eq!(
+            line(&msg(Command::AUTHENTICATE("+".into()))),
+            "AUTHENTICATE +"
+        );
+        assert_eq!(
+            line(&msg(Command::PRIVMSG(
+                "NickServ".into(),
+                "IDENTIFY hunter2".into()
+            ))),
+            "PRIVMSG NickServ <redacted>"
+        );
+        assert_eq!(
+            line(&msg(Command::PRIVMSG("#cats".into(), "meow".into()))),
+            "PRIVMSG #cats meow"
+        );
+    }
+
+    #[test]
+    fn test_redact_sasl() {
+        let mut redactor = Redactor::default();
+        let mut line = |line: &str| {
+            redactor
+                .redact(&line.parse().unwrap())
+                .to_string()
+                .trim_end()
+                .to_string()
+        };
+
+        assert_eq!(
+            line("AUTHENTICATE SCRAM-SHA-256"),
+            "AUTHENTICATE SCRAM-SHA-256"
+        );
+        assert_eq!(line("AUTHENTICATE +"), "AUTHENTICATE +");
+        // payloads looking like a mechanism name are redacted during an exchange
+        assert_eq!(line("AUTHENTICATE QUJD"), "AUTHENTICATE <redacted>");
+        assert_eq!(line("AUTHENTICATE PLAIN"), "AUTHENTICATE <redacted>");
+        assert_eq!(
+            line(":ircd 904 catinator :SASL authentication failed"),
+            ":ircd 904 catinator :SASL authentication failed"
+        );
+
+        // a new exchange starts with a mechanism again
+        assert_eq!(line("AUTHENTICATE PLAIN"), "AUTHENTICATE PLAIN");
+        assert_eq!(line("AUTHENTICATE QUJD"), "AUTHENTICATE <redacted>");
+        line(":ircd 903 catinator :SASL authentication successful");
+
+        // unknown mechanisms are redacted as well
+        assert_eq!(line("AUTHENTICATE HUNTER2"), "AUTHENTICATE <redacted>");
+    }
+
+    #[test]
+    fn test_diff() {
+        let lines = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            diff(&lines(&["a", "b", "c"]), &lines(&["a", "x", "c", "d"])),
+            vec![
+                Change::Same("a".into()),
+                Change::Added("x".into()),
+                Change::Missing("b".into()),
+                Change::Same("c".into()),
+                Change::Added("d".into()),
+            ]
+        );
+        assert_eq!(diff(&[], &[]), vec![]);
+    }
+
+    #[test]
+    fn test_record() {
+        let path =
+            std::env::temp_dir().join(format!("catinator-recording-{}.jsonl", std::process::id()));
+
+        let recorder = Recorder::open(&path).unwrap();
+        recorder.record(
+            Direction::In,
+            &":audron!a@b PRIVMSG #cats ::pet".parse().unwrap(),
+        );
+        recorder.record(Direction::Out, &Command::PASS("hunter2".into()).into());
+
+        let entries = load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::In);
+        assert_eq!(entries[0].line, ":audron!a@b PRIVMSG #cats ::pet");
+        assert_eq!(entries[1].direction, Direction::Out);
+        assert_eq!(entries[1].line, "PASS <redacted>");
+        assert!(!compared(&entries[1].line));
+    }
+}
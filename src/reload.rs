@@ -0,0 +1,467 @@
+//! Reload the config without reconnecting, keeping runtime state like the `sed` history.
+//!
+//! The event loop of the [catinator](crate::catinator) macro reloads the config on SIGHUP,
+//! or when one of the [config files](crate::config::Config::files) changed if
+//! [`reload_interval`](crate::config::Settings::reload_interval) is set.
+//!
+//! Most settings apply immediately, like the prefixes, per channel settings, permissions
+//! and cooldowns. Channels added to or removed from `server.channels` are joined or
+//! parted and a changed nickname is requested with `NICK`, it is applied once the server
+//! confirmed it. Changes to the connection, like the server hostname, and to `state_file`,
+//! `record`, `event_capacity` and `reload_interval` are not applied but logged as needing
+//! a reconnect, [Bot::figment] keeps their old values as well.
+//!
+//! Hooks are notified with a [Reloaded] event, they can extract their config from the
+//! new [Bot::figment] in a subscriber:
+//!
+//! ```
+//! use anyhow::Result;
+//! use catinator::{reload::Reloaded, Bot};
+//! use serde::Deserialize;
+//!
+//! #[derive(Debug, Default, Deserialize)]
+//! pub struct Title {
+//!     #[serde(default)]
+//!     max_length: Option<usize>,
+//! }
+//!
+//! // subscribe("reload_title", "description", catinator::reload::Reloaded, reload_title)
+//! pub fn reload_title(bot: &Bot, event: &Reloaded) -> Result<()> {
+//!     if event.changed.iter().any(|key| key == "plugins.title") {
+//!         let title: Title = bot.plugin_config("title")?;
+//!         tracing::info!("title length is now {:?}", title.max_length);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context as _, Result};
+use figment::{providers::Serialized, Figment};
+use irc::client::prelude::*;
+use tracing::{info, warn};
+
+use crate::{config, Bot};
+
+/// Settings that are only used while connecting or starting the bot
+const RECONNECT: &[&str] = &[
+    "user.username",
+    "user.realname",
+    "user.password",
+    "server.hostname",
+    "server.port",
+    "server.tls",
+    "server.sasl",
+    "server.password",
+    "settings.state_file",
+    "settings.record",
+    "settings.event_capacity",
+    "settings.reload_interval",
+];
+
+/// Event emitted after the config was reloaded
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Reloaded {
+    /// Settings that changed and were applied, like `settings.prefix`
+    pub changed: Vec<String>,
+    /// Settings that changed but need a reconnect to apply, like `server.hostname`
+    pub reconnect: Vec<String>,
+}
+
+/// Keys of the settings that differ between `old` and `new`, like `server.channels`
+fn changes(old: &config::Config, new: &config::Config) -> Result<Vec<String>> {
+    let old = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+
+    let mut changed = Vec::new();
+    for section in ["user", "server", "settings"] {
+        let (old, new) = (&old[section], &new[section]);
+        let keys: BTreeSet<&String> = old
+            .as_object()
+            .into_iter()
+            .chain(new.as_object())
+            .flat_map(|object| object.keys())
+            .collect();
+
+        for key in keys {
+            if old.get(key) != new.get(key) {
+                changed.push(format!("{}.{}", section, key));
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Keys of the `[plugins.<name>]` tables that differ between `old` and `new`
+fn plugin_changes(old: &Figment, new: &Figment) -> Vec<String> {
+    let plugins = |figment: &Figment| {
+        figment
+            .find_value("plugins")
+            .ok()
+            .and_then(|plugins| plugins.into_dict())
+            .unwrap_or_default()
+    };
+    let (old, new) = (plugins(old), plugins(new));
+
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<&String>>()
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| format!("plugins.{}", name))
+        .collect()
+}
+
+/// Keep the settings of `old` in `new` that need a reconnect
+fn keep_connection(old: &config::Config, new: &mut config::Config) {
+    new.user = old.user.clone();
+    new.server = config::Server {
+        channels: new.server.channels.clone(),
+        ..old.server.clone()
+    };
+    new.settings.state_file = old.settings.state_file.clone();
+    new.settings.record = old.settings.record.clone();
+    new.settings.event_capacity = old.settings.event_capacity;
+    new.settings.reload_interval = old.settings.reload_interval;
+}
+
+/// Override the `keys` that need a reconnect in `figment` with their values in `old`,
+/// so it agrees with the config that is applied
+fn keep_figment(figment: Figment, old: &config::Config, keys: &[String]) -> Result<Figment> {
+    let old = serde_json::to_value(old)?;
+
+    Ok(keys.iter().fold(figment, |figment, key| {
+        let value = old
+            .pointer(&format!("/{}", key.replace('.', "/")))
+            .cloned()
+            .unwrap_or_default();
+        figment.merge(Serialized::global(key, value))
+    }))
+}
+
+impl Bot {
+    /// Reload the config from the default sources like [Bot::new], see [reload](self)
+    pub fn reload(&mut self) -> Result<Reloaded> {
+        self.reload_with(config::Config::figment())
+    }
+
+    /// Reload the config from `figment`, see [reload](self).
+    ///
+    /// Nothing is changed if the config can not be extracted.
+    pub fn reload_with(&mut self, figment: Figment) -> Result<Reloaded> {
+        let mut config: config::Config = figment.extract().context("failed to extract config")?;
+
+        let (reconnect, mut changed): (Vec<String>, Vec<String>) = changes(&self.config, &config)?
+            .into_iter()
+            .partition(|key| RECONNECT.contains(&key.as_str()));
+        changed.extend(plugin_changes(&self.figment, &figment));
+
+        // the nickname is only applied once the server confirmed it, see [Bot::observe]
+        let nickname = config.user.nickname.clone();
+        let mut kept = reconnect.clone();
+        kept.push("user.nickname".to_string());
+
+        keep_connection(&self.config, &mut config);
+        self.figment = keep_figment(figment, &self.config, &kept)?;
+        let old = std::mem::replace(&mut self.config, config);
+
+        // the config is applied already, failing to send one command must not stop the others
+        let casemapping = self.casemapping();
+        let contains = |channels: &[String], channel: &str| {
+            channels.iter().any(|other| casemapping.eq(other, channel))
+        };
+        for channel in &self.config.server.channels {
+            if !contains(&old.server.channels, channel) {
+                if let Err(err) = self.send(Command::JOIN(channel.clone(), None, None)) {
+                    warn!("failed to join {}: {}", channel, err);
+                }
+            }
+        }
+        for channel in &old.server.channels {
+            if !contains(&self.config.server.channels, channel) {
+                if let Err(err) = self.send(Command::PART(channel.clone(), None)) {
+                    warn!("failed to part {}: {}", channel, err);
+                }
+            }
+        }
+        if old.user.nickname != nickname {
+            if let Err(err) = self.send(Command::NICK(nickname.clone())) {
+                warn!("failed to change the nickname to {}: {}", nickname, err);
+            }
+        }
+        if old.settings.middleware != self.config.settings.middleware {
+            self.reorder_middleware();
+        }
+
+        if changed.is_empty() {
+            info!("reloaded config, nothing changed");
+        } else {
+            info!("reloaded config, changed {}", changed.join(", "));
+        }
+        if !reconnect.is_empty() {
+            warn!(
+                "changes to {} need a reconnect, restart the bot to apply them",
+                reconnect.join(", ")
+            );
+        }
+
+        let reloaded = Reloaded { changed, reconnect };
+        if let Err(err) = self.emit(reloaded.clone()) {
+            warn!("failed to notify hooks of the reload: {}", err);
+        }
+
+        Ok(reloaded)
+    }
+}
+
+/// Listens for SIGHUP, which never arrives on other platforms
+struct Hangup(#[cfg(unix)] Option<tokio::signal::unix::Signal>);
+
+impl Hangup {
+    fn new() -> Hangup {
+        #[cfg(unix)]
+        let hangup = Hangup(
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .map_err(|err| warn!("failed to listen for SIGHUP: {}", err))
+                .ok(),
+        );
+        #[cfg(not(unix))]
+        let hangup = Hangup();
+
+        hangup
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.0.as_mut() {
+            if signal.recv().await.is_some() {
+                info!("received SIGHUP");
+                return;
+            }
+        }
+
+        std::future::pending().await
+    }
+}
+
+/// Time the file at `path` was last modified, `None` if it does not exist
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Waits until the config should be reloaded, used by the event loop
+pub struct Reloader {
+    hangup: Hangup,
+    interval: Option<tokio::time::Interval>,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Reloader {
+    /// Reload on SIGHUP and when the `files` changed, checking them every `interval`
+    pub fn new(files: Vec<PathBuf>, interval: Option<Duration>) -> Reloader {
+        let interval = interval.map(|period| {
+            let mut interval =
+                tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            interval
+        });
+
+        Reloader {
+            hangup: Hangup::new(),
+            interval,
+            files: files
+                .into_iter()
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+        }
+    }
+
+    /// Check if any of the files changed since the last check
+    fn changed(&mut self) -> bool {
+        let mut changed = false;
+
+        for (path, last) in self.files.iter_mut() {
+            let modified = modified(path);
+            if modified != *last {
+                info!("config file {} changed", path.display());
+                *last = modified;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Wait until the config should be reloaded
+    pub async fn next(&mut self) {
+        loop {
+            let interval = &mut self.interval;
+            let tick = async {
+                match interval.as_mut() {
+                    Some(interval) => {
+                        interval.tick().await;
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = tick => {}
+                _ = self.hangup.recv() => return,
+            }
+
+            if self.changed() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestBot;
+    use figment::providers::{Format, Toml};
+
+    const CONFIG: &str = r##"
+    [user]
+    nickname = "catinator"
+    username = "catinator"
+    realname = "catinator"
+
+    [server]
+    hostname = "irc.example.com"
+    channels = ["#cats", "#dogs"]
+
+    [settings]
+    "##;
+
+    #[test]
+    fn test_changes() {
+        let old: config::Config = Figment::from(Toml::string(CONFIG)).extract().unwrap();
+        let mut new = old.clone();
+        assert_eq!(changes(&old, &new).unwrap(), Vec::<String>::new());
+
+        new.server.hostname = "irc.example.org".to_string();
+        new.settings.prefix = vec!["!".to_string()];
+        new.settings.reload_interval = Some(Duration::from_secs(5));
+        assert_eq!(
+            changes(&old, &new).unwrap(),
+            [
+                "server.hostname",
+                "settings.prefix",
+                "settings.reload_interval"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let mut bot = TestBot::with_config(CONFIG).await.unwrap();
+        let mut events = bot.events.take_receiver().unwrap();
+
+        let figment = Figment::from(Toml::string(CONFIG)).merge(Toml::string(
+            r##"
+            [user]
+            nickname = "catinator2"
+            password = "hunter2"
+            [server]
+            hostname = "irc.example.org"
+            port = 6667
+            channels = ["#Cats", "#birds"]
+            [settings]
+            prefix = "!"
+            [plugins.title]
+            max_length = 5
+            "##,
+        ));
+        let reloaded = bot.reload_with(figment).unwrap();
+
+        assert_eq!(
+            reloaded.changed,
+            [
+                "user.nickname",
+                "server.channels",
+                "settings.prefix",
+                "plugins.title"
+            ]
+        );
+        assert_eq!(
+            reloaded.reconnect,
+            ["user.password", "server.hostname", "server.port"]
+        );
+
+        assert_eq!(bot.config.settings.prefix, ["!"]);
+        assert_eq!(bot.config.server.hostname, "irc.example.com");
+        assert_eq!(bot.config.server.port, 6697);
+
+        // the figment agrees with the applied config
+        let applied: config::Config = bot.figment().extract().unwrap();
+        assert_eq!(
+            serde_json::to_value(&applied).unwrap(),
+            serde_json::to_value(&bot.config).unwrap()
+        );
+        let title: toml::Value = bot.plugin_config("title").unwrap();
+        assert_eq!(title["max_length"].as_integer(), Some(5));
+
+        bot.assert_sent(Command::JOIN("#birds".to_string(), None, None));
+        bot.assert_sent(Command::PART("#dogs".to_string(), None));
+        bot.assert_sent(Command::NICK("catinator2".to_string()));
+        assert_eq!(bot.take_sent().len(), 3);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.downcast_ref::<Reloaded>(), Some(&reloaded));
+
+        // the nickname is kept until the server confirmed it
+        assert_eq!(bot.config.user.nickname, "catinator");
+        let taken = ":ircd 433 catinator catinator2 :Nickname is already in use";
+        bot.observe(&taken.parse().unwrap());
+        assert_eq!(bot.config.user.nickname, "catinator");
+        bot.observe(&":mett!mett@example.com NICK catinator2".parse().unwrap());
+        assert_eq!(bot.config.user.nickname, "catinator");
+
+        bot.observe(
+            &":catinator!bot@example.com NICK catinator2"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(bot.config.user.nickname, "catinator2");
+        let applied: config::Config = bot.figment().extract().unwrap();
+        assert_eq!(applied.user.nickname, "catinator2");
+
+        // an invalid config changes nothing
+        let figment = Figment::from(Toml::string("[settings]\nprefix = 1"));
+        assert!(bot.reload_with(figment).is_err());
+        assert_eq!(bot.config.settings.prefix, ["!"]);
+        bot.assert_silent();
+    }
+
+    #[test]
+    fn test_changed() {
+        let path =
+            std::env::temp_dir().join(format!("catinator-reload-{}.toml", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let mut reloader = Reloader::new(vec![path.clone()], None);
+        assert!(!reloader.changed());
+
+        std::fs::write(&path, "[settings]").unwrap();
+        assert!(reloader.changed());
+        assert!(!reloader.changed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(reloader.changed());
+    }
+}
@@ -0,0 +1,361 @@
+//! Periodic tasks run with the `interval` and `cron` items of the [catinator](crate::catinator) macro.
+//!
+//! Tasks run alongside the message loop, only one task or message is handled at a time.
+//! If the bot is busy when a task is due, it runs as soon as possible and the
+//! [Missed] policy decides what happens to the ticks that were missed in the meantime.
+//!
+//! Cron expressions have the usual five fields `minute hour day month weekday`,
+//! supporting `*`, lists `1,15`, ranges `1-5` and steps `*/15`, in the local timezone.
+//! Sunday is weekday `0` or `7`. If both day and weekday are restricted either has to match.
+//! Days that never occur in the given months, like `0 0 31 2 *`, are rejected.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone};
+use tracing::{debug, warn};
+
+/// What to do with ticks that were missed while the bot was busy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Missed {
+    /// Run once for all missed ticks, then continue on schedule
+    #[default]
+    Skip,
+    /// Run once for every missed tick, as fast as possible
+    Burst,
+    /// Run once and start the next interval from now, for intervals only
+    Delay,
+}
+
+/// A parsed cron expression, see the [module documentation](self)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// The most days each month can have, counting the 29th of february
+const DAYS_IN_MONTH: [u32; 12] = [31, 29, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+
+/// Parse a cron field into a bitset of the allowed values in `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse().context("invalid step")?),
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                None if part.contains('/') => (range.parse()?, max),
+                None => {
+                    let value = range.parse()?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start < min || end > max || start > end || step == 0 {
+            bail!("{} is out of range {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+impl FromStr for Cron {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("expected 5 fields: minute hour day month weekday");
+        };
+
+        let mut weekday_set = parse_field(weekdays, 0, 7).context("invalid weekday")?;
+        // sunday can be 0 or 7
+        if weekday_set & 1 << 7 != 0 {
+            weekday_set |= 1;
+        }
+
+        let cron = Cron {
+            minutes: parse_field(minutes, 0, 59).context("invalid minute")?,
+            hours: parse_field(hours, 0, 23).context("invalid hour")?,
+            days: parse_field(days, 1, 31).context("invalid day")?,
+            months: parse_field(months, 1, 12).context("invalid month")?,
+            weekdays: weekday_set,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        };
+
+        // only the day is restricted, it has to exist in one of the months
+        let exists = (1..=12).any(|month| {
+            cron.months & 1 << month != 0
+                && cron.days & ((1 << (DAYS_IN_MONTH[month - 1] + 1)) - 1) != 0
+        });
+        if !cron.any_day && cron.any_weekday && !exists {
+            bail!("day {:?} never occurs in month {:?}", days, months);
+        }
+
+        Ok(cron)
+    }
+}
+
+impl Cron {
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        let day = self.days & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().num_days_from_sunday() != 0;
+
+        let day = match (self.any_day, self.any_weekday) {
+            (true, _) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        };
+
+        day && self.months & 1 << date.month() != 0
+    }
+
+    /// The first time matching the expression after `after`
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let after = after.naive_local();
+        let start = after.date();
+
+        // a matching date is at most a few years away, e.g. the 29th of february
+        for date in start.iter_days().take(366 * 8) {
+            if !self.matches_date(date) {
+                continue;
+            }
+
+            for hour in (0..24).filter(|hour| self.hours & 1 << hour != 0) {
+                for minute in (0..60).filter(|minute| self.minutes & 1 << minute != 0) {
+                    let time = date.and_time(NaiveTime::from_hms_opt(hour, minute, 0)?);
+                    if time <= after {
+                        continue;
+                    }
+
+                    // times skipped by daylight saving time do not exist
+                    if let Some(time) = tz.from_local_datetime(&time).earliest() {
+                        return Some(time);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// When a task runs
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Interval(Duration),
+    Cron(Box<Cron>),
+}
+
+impl Schedule {
+    /// The first tick after `after`, which must not be in the future
+    fn next_after(&self, after: Instant) -> Option<Instant> {
+        match self {
+            Schedule::Interval(period) => Some(after + *period),
+            Schedule::Cron(cron) => {
+                let ago = chrono::Duration::from_std(after.elapsed()).ok()?;
+                let wall = Local::now() - ago;
+                let next = cron.next_after(&wall)?;
+                Some(after + (next - wall).to_std().ok()?)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Task {
+    name: &'static str,
+    schedule: Schedule,
+    missed: Missed,
+    next: Option<Instant>,
+}
+
+impl Task {
+    /// Schedule the next run after the tick `due` ran at `now`
+    fn advance(&mut self, due: Instant, now: Instant) {
+        self.next = match self.missed {
+            Missed::Burst => self.schedule.next_after(due),
+            Missed::Delay => self.schedule.next_after(now),
+            Missed::Skip => {
+                let mut next = self.schedule.next_after(due);
+                while let Some(tick) = next.filter(|tick| *tick <= now) {
+                    next = self.schedule.next_after(tick);
+                }
+                next
+            }
+        };
+    }
+}
+
+/// Keeps track of when tasks are due
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    fn add(&mut self, name: &'static str, schedule: Schedule, missed: Missed) {
+        let next = schedule.next_after(Instant::now());
+        if next.is_none() {
+            warn!(target: "task", "{} is never due, its schedule does not match any time", name);
+        }
+        self.tasks.push(Task {
+            name,
+            schedule,
+            missed,
+            next,
+        });
+    }
+
+    /// Run the task `name` every `period`, starting one `period` from now
+    pub fn interval(&mut self, name: &'static str, period: Duration, missed: Missed) {
+        self.add(name, Schedule::Interval(period), missed)
+    }
+
+    /// Run the task `name` at the times matching the cron `expression`
+    pub fn cron(&mut self, name: &'static str, expression: &str, missed: Missed) -> Result<()> {
+        let cron = expression
+            .parse()
+            .with_context(|| format!("invalid cron expression {:?} of {}", expression, name))?;
+        self.add(name, Schedule::Cron(Box::new(cron)), missed);
+        Ok(())
+    }
+
+    /// Wait until the next task is due and return its name.
+    ///
+    /// Never completes if there are no tasks, cancelling it does not lose ticks.
+    pub async fn next(&mut self) -> &'static str {
+        let next = self
+            .tasks
+            .iter_mut()
+            .filter(|task| task.next.is_some())
+            .min_by_key(|task| task.next);
+
+        let task = match next {
+            Some(task) => task,
+            None => return std::future::pending().await,
+        };
+
+        let due = task.next.unwrap_or_else(Instant::now);
+        tokio::time::sleep_until(due.into()).await;
+
+        task.advance(due, Instant::now());
+
+        debug!(target: "task", "{} is due, next run at {:?}", task.name, task.next);
+        task.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Utc;
+
+    fn time(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_cron() {
+        assert!("0 9 * * 1".parse::<Cron>().is_ok());
+        assert!("*/15 8-18 1,15 * 1-5".parse::<Cron>().is_ok());
+        assert!("0 9 * *".parse::<Cron>().is_err());
+        assert!("60 9 * * *".parse::<Cron>().is_err());
+        assert!("0 9 0 * *".parse::<Cron>().is_err());
+        assert!("*/0 * * * *".parse::<Cron>().is_err());
+        assert!("a * * * *".parse::<Cron>().is_err());
+
+        // days that never occur in the month
+        assert!("0 0 31 2 *".parse::<Cron>().is_err());
+        assert!("0 0 30,31 2 *".parse::<Cron>().is_err());
+        assert!("0 0 31 4,6 *".parse::<Cron>().is_err());
+        assert!("0 0 31 2,3 *".parse::<Cron>().is_ok());
+        assert!("0 0 29 2 *".parse::<Cron>().is_ok());
+        // either the day or the weekday has to match
+        assert!("0 0 31 2 1".parse::<Cron>().is_ok());
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        // monday 9:00
+        let cron: Cron = "0 9 * * 1".parse().unwrap();
+        assert_eq!(
+            cron.next_after(&time("2024-05-01T12:00:00Z")),
+            Some(time("2024-05-06T09:00:00Z"))
+        );
+        assert_eq!(
+            cron.next_after(&time("2024-05-06T09:00:00Z")),
+            Some(time("2024-05-13T09:00:00Z"))
+        );
+
+        let cron: Cron = "*/15 * * * *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(&time("2024-05-01T12:07:30Z")),
+            Some(time("2024-05-01T12:15:00Z"))
+        );
+
+        // sunday as 7, or the first of the month
+        let cron: Cron = "30 0 1 * 7".parse().unwrap();
+        assert_eq!(
+            cron.next_after(&time("2024-05-01T12:00:00Z")),
+            Some(time("2024-05-05T00:30:00Z"))
+        );
+        assert_eq!(
+            cron.next_after(&time("2024-05-26T12:00:00Z")),
+            Some(time("2024-06-01T00:30:00Z"))
+        );
+
+        let cron: Cron = "0 0 29 2 *".parse().unwrap();
+        assert_eq!(
+            cron.next_after(&time("2024-03-01T00:00:00Z")),
+            Some(time("2028-02-29T00:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_missed() {
+        let second = Duration::from_secs(1);
+        let start = Instant::now();
+
+        let next = |missed| {
+            let mut task = Task {
+                name: "task",
+                schedule: Schedule::Interval(10 * second),
+                missed,
+                next: Some(start + 10 * second),
+            };
+            // the bot was busy until 35 seconds after the start
+            task.advance(start + 10 * second, start + 35 * second);
+            task.next.unwrap() - start
+        };
+
+        assert_eq!(next(Missed::Skip), 40 * second);
+        assert_eq!(next(Missed::Burst), 20 * second);
+        assert_eq!(next(Missed::Delay), 45 * second);
+    }
+}
@@ -0,0 +1,253 @@
+//! Load secrets like passwords and api keys from files.
+//!
+//! Every secret setting can also be given as a path with a `_file` suffix, like
+//! `user.password_file` or `plugins.wolfram_alpha.api_key_file`, the file is read when the config is loaded
+//! and its trimmed contents are used as the value. This works with secrets mounted by
+//! kubernetes or the credentials of systemd, relative paths are looked up in the
+//! `$CREDENTIALS_DIRECTORY` set by `LoadCredential=` if it is set.
+//!
+//! ```toml
+//! [default.user]
+//! password_file = "/run/secrets/catinator-password"
+//! ```
+//!
+//! The file takes precedence over the value if both are set. A warning is logged
+//! if the file can be read by anyone.
+//!
+//! Secrets are settings with `password`, `key`, `secret` or `token` as one of the
+//! `_` separated words of their name, like `api_key`.
+
+use std::path::{Path, PathBuf};
+
+use figment::{
+    value::{Dict, Map, Value},
+    Error, Figment, Metadata, Profile, Provider,
+};
+use tracing::warn;
+
+/// Suffix of settings containing the path to a secret
+const SUFFIX: &str = "_file";
+
+/// Whether the value of `key` is a secret, like `user.password` or `wa_api_key`.
+///
+/// One of the `_` separated words of the name has to be a secret, so `monkey_mode` is not.
+pub fn is_secret(key: &str) -> bool {
+    let name = key.rsplit('.').next().unwrap_or(key).to_ascii_lowercase();
+    !name.ends_with(SUFFIX)
+        && name
+            .split('_')
+            .any(|word| ["password", "key", "secret", "token"].contains(&word))
+}
+
+/// Whether the file with `metadata` can be read by anyone
+#[cfg(unix)]
+fn world_readable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o004 != 0
+}
+
+#[cfg(not(unix))]
+fn world_readable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Resolve a relative `path` in the systemd credentials directory if there is one
+fn credential_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+
+    match std::env::var_os("CREDENTIALS_DIRECTORY") {
+        Some(directory) if path.is_relative() => Path::new(&directory).join(path),
+        _ => path,
+    }
+}
+
+/// A secret read from a file, provides the value for `key`
+struct SecretFile {
+    key: String,
+    path: PathBuf,
+    value: Result<String, String>,
+}
+
+impl SecretFile {
+    fn read(key: String, path: PathBuf) -> SecretFile {
+        let value = std::fs::File::open(&path)
+            .and_then(|file| {
+                if world_readable(&file.metadata()?) {
+                    warn!(
+                        "secret file {} for {} is readable by anyone, restrict it with `chmod o-r`",
+                        path.display(),
+                        key
+                    );
+                }
+                std::io::read_to_string(file)
+            })
+            .map(|value| value.trim().to_string())
+            .map_err(|err| {
+                format!(
+                    "failed to read secret file {} for {}: {}",
+                    path.display(),
+                    key,
+                    err
+                )
+            });
+
+        SecretFile { key, path, value }
+    }
+}
+
+impl Provider for SecretFile {
+    fn metadata(&self) -> Metadata {
+        Metadata::from("secret file", self.path.as_path())
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let value = self.value.clone().map_err(Error::from)?;
+
+        match figment::util::nest(&self.key, Value::from(value)) {
+            Value::Dict(_, dict) => Ok(Profile::Global.collect(dict)),
+            _ => Err(Error::from(format!("invalid secret key {}", self.key))),
+        }
+    }
+}
+
+/// Collect the `(key, path)` of all secret files in `value`
+fn collect(prefix: &str, value: &Value, files: &mut Vec<(String, PathBuf)>) {
+    let dict = match value {
+        Value::Dict(_, dict) => dict,
+        _ => return,
+    };
+
+    for (key, value) in dict {
+        let path = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        match (path.strip_suffix(SUFFIX), value.as_str()) {
+            (Some(secret), Some(file)) if is_secret(secret) => {
+                files.push((secret.to_string(), credential_path(file)))
+            }
+            _ => collect(&path, value, files),
+        }
+    }
+}
+
+/// Read the secrets of all `*_file` settings of the selected profile in `figment`,
+/// see the [module documentation](self).
+///
+/// This is done by [Config::figment](crate::config::Config::figment), use it on
+/// figments that are built otherwise. Files that can not be read fail the extraction.
+pub fn resolve_files(figment: Figment) -> Figment {
+    // an invalid config fails when it is extracted later
+    let value: Value = match figment.extract() {
+        Ok(value) => value,
+        Err(_) => return figment,
+    };
+
+    let mut files = Vec::new();
+    collect("", &value, &mut files);
+
+    files.into_iter().fold(figment, |figment, (key, path)| {
+        figment.merge(SecretFile::read(key, path))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use figment::providers::{Format, Toml};
+
+    use crate::config::Config;
+
+    const CONFIG: &str = r##"
+    [user]
+    nickname = "catinator"
+    username = "catinator"
+    realname = "catinator"
+
+    [server]
+    hostname = "irc.example.com"
+
+    [settings]
+    state_file = "state.json"
+    "##;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("catinator-secret-{}-{}", name, std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_is_secret() {
+        assert!(is_secret("user.password"));
+        assert!(is_secret("wa_api_key"));
+        assert!(!is_secret("user.password_file"));
+        assert!(!is_secret("settings.state"));
+        assert!(is_secret("plugins.github.token"));
+        assert!(is_secret("plugins.oauth.client_secret"));
+        assert!(is_secret("plugins.wolfram_alpha.API_KEY"));
+        assert!(!is_secret("plugins.zoo.monkey_mode"));
+        assert!(!is_secret("plugins.zoo.keyboard"));
+        assert!(!is_secret("plugins.zoo.tokens_used"));
+    }
+
+    #[test]
+    fn test_resolve_files() {
+        let password = temp_file("password", "hunter2\n");
+        let key = temp_file("key", " meow ");
+
+        let figment = Figment::from(Toml::string(CONFIG)).merge(Toml::string(&format!(
+            "wa_api_key_file = {:?}\n[user]\npassword = 'nope'\npassword_file = {:?}",
+            key, password
+        )));
+        let figment = resolve_files(figment);
+
+        let config: Config = figment.extract().unwrap();
+        assert_eq!(config.user.password.as_deref(), Some("hunter2"));
+        assert_eq!(
+            config.settings.state_file,
+            Some(PathBuf::from("state.json"))
+        );
+        assert_eq!(
+            figment.extract_inner::<String>("wa_api_key").unwrap(),
+            "meow"
+        );
+        assert_eq!(
+            figment.find_metadata("user.password").unwrap().name,
+            "secret file"
+        );
+
+        std::fs::remove_file(password).unwrap();
+        std::fs::remove_file(key).unwrap();
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let figment = Figment::from(Toml::string(CONFIG)).merge(Toml::string(
+            "[server]\npassword_file = '/nonexistent/catinator'",
+        ));
+
+        let err = resolve_files(figment).extract::<Config>().unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("failed to read secret file /nonexistent/catinator for server.password"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_world_readable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_file("mode", "hunter2");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(world_readable(&std::fs::metadata(&path).unwrap()));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(!world_readable(&std::fs::metadata(&path).unwrap()));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,218 @@
+//! Runtime state of the bot that is changed through chat commands.
+//!
+//! The state is persisted as json to the file set in
+//! [`settings.state_file`](crate::config::Settings::state_file) so it survives restarts.
+//! If no file is configured the state is only kept in memory.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::util::CaseMapping;
+
+/// The persisted runtime state
+#[derive(Debug)]
+pub struct State {
+    path: Option<PathBuf>,
+    data: Mutex<Data>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct Data {
+    /// Names of the commands, matchers and hooks disabled per channel
+    #[serde(default)]
+    disabled: BTreeMap<String, BTreeSet<String>>,
+    /// Nicks, hostmasks and accounts ignored with the `ignore` command
+    #[serde(default)]
+    ignored: BTreeSet<String>,
+}
+
+impl State {
+    /// Load the state from `path`, starting with an empty state if the file does not exist yet.
+    pub fn load(path: Option<PathBuf>) -> Result<State> {
+        let data = match &path {
+            Some(path) if path.exists() => {
+                debug!("loading state from: {}", path.display());
+                let file = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read state file {}", path.display()))?;
+                serde_json::from_str(&file)
+                    .with_context(|| format!("failed to parse state file {}", path.display()))?
+            }
+            _ => Data::default(),
+        };
+
+        Ok(State {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    /// A state that is only kept in memory
+    pub fn in_memory() -> State {
+        State {
+            path: None,
+            data: Mutex::new(Data::default()),
+        }
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        // The data is always left consistent, so a poisoned lock is still usable
+        self.data.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn save(&self, data: &Data) -> Result<()> {
+        if let Some(path) = &self.path {
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, serde_json::to_string_pretty(data)?)
+                .with_context(|| format!("failed to write state file {}", tmp.display()))?;
+            std::fs::rename(&tmp, path)
+                .with_context(|| format!("failed to write state file {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Check if the command, matcher or hook `name` is enabled in `channel`,
+    /// channels are compared using the servers `casemapping`
+    pub fn is_enabled(&self, channel: &str, name: &str, casemapping: CaseMapping) -> bool {
+        !self
+            .data()
+            .disabled
+            .get(&casemapping.lowercase(channel))
+            .is_some_and(|disabled| disabled.contains(name))
+    }
+
+    /// Enable or disable `name` in `channel` and persist the change.
+    ///
+    /// Returns false if `name` already was in the requested state.
+    pub fn set_enabled(
+        &self,
+        channel: &str,
+        name: &str,
+        enabled: bool,
+        casemapping: CaseMapping,
+    ) -> Result<bool> {
+        let mut data = self.data();
+        let channel = casemapping.lowercase(channel);
+
+        let changed = if enabled {
+            let changed = data
+                .disabled
+                .get_mut(&channel)
+                .is_some_and(|disabled| disabled.remove(name));
+            data.disabled.retain(|_, disabled| !disabled.is_empty());
+            changed
+        } else {
+            data.disabled
+                .entry(channel)
+                .or_default()
+                .insert(name.to_string())
+        };
+
+        if changed {
+            self.save(&data)?;
+        }
+
+        Ok(changed)
+    }
+
+    /// Names of everything that is disabled in `channel`
+    pub fn disabled(&self, channel: &str, casemapping: CaseMapping) -> BTreeSet<String> {
+        self.data()
+            .disabled
+            .get(&casemapping.lowercase(channel))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Entries ignored at runtime, see [permission::ignore_matches](crate::permission::ignore_matches)
+    pub fn ignored(&self) -> BTreeSet<String> {
+        self.data().ignored.clone()
+    }
+
+    /// Add or remove `entry` from the ignore list and persist the change.
+    ///
+    /// Returns false if `entry` already was in the requested state.
+    pub fn set_ignored(&self, entry: &str, ignored: bool) -> Result<bool> {
+        let mut data = self.data();
+        let entry = entry.to_lowercase();
+
+        let changed = if ignored {
+            data.ignored.insert(entry)
+        } else {
+            data.ignored.remove(&entry)
+        };
+
+        if changed {
+            self.save(&data)?;
+        }
+
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle() {
+        let state = State::in_memory();
+        let rfc1459 = CaseMapping::Rfc1459;
+        assert!(state.is_enabled("#chan", "nitter", rfc1459));
+
+        assert!(state
+            .set_enabled("#chan", "nitter", false, rfc1459)
+            .unwrap());
+        assert!(!state
+            .set_enabled("#Chan", "nitter", false, rfc1459)
+            .unwrap());
+        assert!(!state.is_enabled("#CHAN", "nitter", rfc1459));
+        assert!(state.is_enabled("#other", "nitter", rfc1459));
+
+        assert!(state.set_enabled("#chan", "nitter", true, rfc1459).unwrap());
+        assert!(state.is_enabled("#chan", "nitter", rfc1459));
+        assert!(state.disabled("#chan", rfc1459).is_empty());
+
+        // channels differing in the rfc1459 special characters are the same
+        assert!(state
+            .set_enabled("#cats[]", "nitter", false, rfc1459)
+            .unwrap());
+        assert!(!state.is_enabled("#CATS{}", "nitter", rfc1459));
+        assert!(state.is_enabled("#CATS[]", "nitter", CaseMapping::Ascii));
+        assert_eq!(state.disabled("#cats{}", rfc1459).len(), 1);
+    }
+
+    #[test]
+    fn test_ignore() {
+        let state = State::in_memory();
+
+        assert!(state.set_ignored("RelayBot", true).unwrap());
+        assert!(!state.set_ignored("relaybot", true).unwrap());
+        assert_eq!(state.ignored(), BTreeSet::from(["relaybot".to_string()]));
+
+        assert!(state.set_ignored("relaybot", false).unwrap());
+        assert!(!state.set_ignored("relaybot", false).unwrap());
+        assert!(state.ignored().is_empty());
+    }
+
+    #[test]
+    fn test_persist() {
+        let path =
+            std::env::temp_dir().join(format!("catinator-state-{}.json", std::process::id()));
+
+        let state = State::load(Some(path.clone())).unwrap();
+        state
+            .set_enabled("#chan", "shifty_eyes", false, CaseMapping::Rfc1459)
+            .unwrap();
+
+        let state = State::load(Some(path.clone())).unwrap();
+        assert!(!state.is_enabled("#chan", "shifty_eyes", CaseMapping::Rfc1459));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
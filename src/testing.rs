@@ -0,0 +1,306 @@
+//! Helpers to unit test commands, matchers and hooks without an irc server.
+//!
+//! Only available with the `testing` feature, enable it for your tests with
+//! `catinator = { version = "1", features = ["testing"] }` in `[dev-dependencies]`.
+//!
+//! A [TestBot] is a [Bot] that records everything it sends instead of sending it,
+//! it dereferences to the [Bot] so it can be passed to handlers directly.
+//! Messages to feed the handlers can be built with [privmsg] and friends.
+//!
+//! ```
+//! use catinator::testing::{privmsg, TestBot};
+//! use catinator::{assert_replied, Context};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let bot = TestBot::new().await;
+//!
+//! let msg = privmsg("audron", "#cats", ":pet");
+//! catinator::hooks::pet(&bot, Context::new(&bot, &msg)).unwrap();
+//!
+//! assert_eq!(bot.sent().len(), 1);
+//!
+//! let msg = privmsg("audron", "#cats", ":about");
+//! catinator::hooks::about(&bot, Context::new(&bot, &msg)).unwrap();
+//! assert_replied!(
+//!     bot,
+//!     "#cats",
+//!     "catinator is catinator - https://gitlab.com/cocainefarm/gnulag/catinator"
+//! );
+//! # }
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use anyhow::Result;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use futures::StreamExt;
+
+pub use irc::client::prelude::{Command, Message, Prefix};
+
+use crate::{record::Outbox, Bot};
+
+/// Config of a [TestBot], merged with the one passed to [TestBot::with_config]
+const DEFAULT_CONFIG: &str = r#"
+[user]
+nickname = "catinator"
+username = "catinator"
+realname = "catinator"
+
+[server]
+hostname = "irc.example.com"
+
+[settings]
+"#;
+
+/// A [Bot] without a connection that records the messages it sends
+pub struct TestBot {
+    bot: Bot,
+    outbox: Arc<Outbox>,
+}
+
+impl TestBot {
+    /// Create a bot with the nick `catinator` and the default settings
+    pub async fn new() -> TestBot {
+        TestBot::with_config("")
+            .await
+            .expect("the default test config is valid")
+    }
+
+    /// Create a bot with the settings in `toml`, which uses the same keys as `config.toml`
+    /// without the profiles, like `[settings]` or `[user]`
+    pub async fn with_config(toml: &str) -> Result<TestBot> {
+        let figment = Figment::from(Toml::string(DEFAULT_CONFIG)).merge(Toml::string(toml));
+
+        let outbox = Arc::new(Outbox::default());
+        let mut bot = Bot::offline(figment).await?;
+        bot.outbox = Some(outbox.clone());
+
+        Ok(TestBot { bot, outbox })
+    }
+
+    /// Make `messages` the incoming messages read by the event loop of the
+    /// [catinator](crate::catinator) macro, which ends after the last one.
+    ///
+    /// ```
+    /// use catinator::testing::{privmsg, TestBot};
+    /// use catinator::{assert_replied, Context};
+    ///
+    /// fn meow(_bot: &catinator::Bot, ctx: Context) -> anyhow::Result<()> {
+    ///     ctx.reply("meow")
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let mut bot = TestBot::new().await;
+    /// bot.feed([privmsg("audron", "#cats", ":meow")]);
+    ///
+    /// catinator::catinator![command("meow", "Meow back", meow)];
+    ///
+    /// assert_replied!(bot, "#cats", "meow");
+    /// # }
+    /// ```
+    pub fn feed(&mut self, messages: impl IntoIterator<Item = Message>) {
+        let messages: Vec<Message> = messages.into_iter().collect();
+        *self
+            .bot
+            .incoming
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner()) = Some(futures::stream::iter(messages).boxed());
+    }
+
+    /// Everything sent so far
+    pub fn sent(&self) -> Vec<Message> {
+        self.outbox.sent()
+    }
+
+    /// Everything sent so far, clearing the recorded messages
+    pub fn take_sent(&self) -> Vec<Message> {
+        self.outbox.take()
+    }
+
+    /// Assert that `command` was sent, listing everything that was sent otherwise
+    #[track_caller]
+    pub fn assert_sent(&self, command: Command) {
+        let sent = self.sent();
+        if !sent.iter().any(|msg| msg.command == command) {
+            panic!(
+                "expected {:?} to be sent, but got:\n{}",
+                command,
+                sent.iter()
+                    .map(|msg| format!("  {:?}", msg.command))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )
+        }
+    }
+
+    /// Assert that nothing was sent
+    #[track_caller]
+    pub fn assert_silent(&self) {
+        let sent = self.sent();
+        assert!(
+            sent.is_empty(),
+            "expected nothing to be sent, but got {:?}",
+            sent
+        );
+    }
+}
+
+impl Deref for TestBot {
+    type Target = Bot;
+
+    fn deref(&self) -> &Bot {
+        &self.bot
+    }
+}
+
+impl DerefMut for TestBot {
+    fn deref_mut(&mut self) -> &mut Bot {
+        &mut self.bot
+    }
+}
+
+/// Build a message with the `command` sent by `nick`
+pub fn message(nick: &str, command: Command) -> Message {
+    Message {
+        tags: None,
+        prefix: Some(Prefix::Nickname(
+            nick.to_string(),
+            nick.to_string(),
+            "example.com".to_string(),
+        )),
+        command,
+    }
+}
+
+/// Build a PRIVMSG with `text` sent by `nick` to `target`, a channel or the bots nick
+pub fn privmsg(nick: &str, target: &str, text: &str) -> Message {
+    message(nick, Command::PRIVMSG(target.to_string(), text.to_string()))
+}
+
+/// Build a NOTICE with `text` sent by `nick` to `target`, a channel or the bots nick
+pub fn notice(nick: &str, target: &str, text: &str) -> Message {
+    message(nick, Command::NOTICE(target.to_string(), text.to_string()))
+}
+
+/// Add the services `account` of the sender to `msg` as the `account` tag
+pub fn with_account(mut msg: Message, account: &str) -> Message {
+    msg.tags
+        .get_or_insert_with(Vec::new)
+        .push(irc::proto::message::Tag(
+            "account".to_string(),
+            Some(account.to_string()),
+        ));
+    msg
+}
+
+/// Assert that a [TestBot] sent a PRIVMSG to a target, the text takes format arguments
+///
+/// ```
+/// use catinator::{assert_replied, testing::TestBot};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let bot = TestBot::new().await;
+/// let nick = "audron";
+///
+/// bot.send_privmsg("#cats", "hello audron").unwrap();
+/// assert_replied!(bot, "#cats", "hello {}", nick);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_replied {
+    ($bot:expr, $target:expr, $($text:tt)+) => {
+        $bot.assert_sent($crate::testing::Command::PRIVMSG(
+            ($target).to_string(),
+            format!($($text)+),
+        ))
+    };
+}
+
+/// Assert that a [TestBot] sent a NOTICE to a target, the text takes format arguments
+///
+/// ```
+/// use catinator::{assert_noticed, testing::TestBot};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let bot = TestBot::new().await;
+///
+/// bot.send_notice("audron", "usage: wa <query>").unwrap();
+/// assert_noticed!(bot, "audron", "usage: wa <query>");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_noticed {
+    ($bot:expr, $target:expr, $($text:tt)+) => {
+        $bot.assert_sent($crate::testing::Command::NOTICE(
+            ($target).to_string(),
+            format!($($text)+),
+        ))
+    };
+}
+
+/// Assert that a [TestBot] sent an action (`/me`) to a target, the text takes format arguments
+///
+/// ```
+/// use catinator::{assert_acted, testing::TestBot};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let bot = TestBot::new().await;
+///
+/// bot.send_action("#cats", "purrs").unwrap();
+/// assert_acted!(bot, "#cats", "purrs");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_acted {
+    ($bot:expr, $target:expr, $($text:tt)+) => {
+        $bot.assert_sent($crate::testing::Command::PRIVMSG(
+            ($target).to_string(),
+            format!("\u{001}ACTION {}\u{001}", format_args!($($text)+)),
+        ))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Context;
+
+    #[tokio::test]
+    async fn test_bot() {
+        let bot = TestBot::with_config("[settings]\nprefix = '!'")
+            .await
+            .unwrap();
+        assert_eq!(bot.config.settings.prefix, ["!"]);
+
+        let msg = with_account(privmsg("audron", "#cats", "!meow"), "audron");
+        assert_eq!(crate::permission::account(&msg), Some("audron"));
+
+        let ctx = Context::new(&bot, &msg);
+        ctx.reply("meow\r\nmeow").unwrap();
+        ctx.reply_notice("psst").unwrap();
+        ctx.reply_action("purrs").unwrap();
+
+        assert_replied!(bot, "#cats", "meow");
+        assert_noticed!(bot, "audron", "{}", "psst");
+        assert_acted!(bot, "#cats", "purrs");
+        assert_eq!(bot.take_sent().len(), 4);
+        bot.assert_silent();
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected NOTICE")]
+    async fn test_assert_sent() {
+        let bot = TestBot::new().await;
+        bot.send_privmsg("#cats", "meow").unwrap();
+        assert_noticed!(bot, "#cats", "meow");
+    }
+}
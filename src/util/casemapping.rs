@@ -0,0 +1,76 @@
+/// How the server compares nicknames and channel names, as announced
+/// in the `CASEMAPPING` token of `RPL_ISUPPORT`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaseMapping {
+    /// Only `A-Z` are lowercased to `a-z`
+    Ascii,
+    /// Additionally `[]\~` are the uppercase versions of `{}|^`, the default
+    #[default]
+    Rfc1459,
+    /// Like rfc1459 but without `~` and `^`
+    StrictRfc1459,
+}
+
+impl CaseMapping {
+    /// Parse the value of the `CASEMAPPING` token, unknown values fall back to the default
+    pub fn from_isupport(value: &str) -> CaseMapping {
+        match value {
+            "ascii" => CaseMapping::Ascii,
+            "strict-rfc1459" => CaseMapping::StrictRfc1459,
+            _ => CaseMapping::Rfc1459,
+        }
+    }
+
+    /// Lowercase a single character
+    pub fn to_lower(&self, c: char) -> char {
+        match (self, c) {
+            (_, 'A'..='Z') => c.to_ascii_lowercase(),
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, '[') => '{',
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, ']') => '}',
+            (CaseMapping::Rfc1459 | CaseMapping::StrictRfc1459, '\\') => '|',
+            (CaseMapping::Rfc1459, '~') => '^',
+            _ => c,
+        }
+    }
+
+    /// Lowercase a nickname or channel name
+    pub fn lowercase(&self, input: &str) -> String {
+        input.chars().map(|c| self.to_lower(c)).collect()
+    }
+
+    /// Compare two nicknames or channel names
+    pub fn eq(&self, a: &str, b: &str) -> bool {
+        a.chars().count() == b.chars().count()
+            && a.chars()
+                .zip(b.chars())
+                .all(|(a, b)| self.to_lower(a) == self.to_lower(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaseMapping;
+
+    #[test]
+    fn test_rfc1459() {
+        let mapping = CaseMapping::Rfc1459;
+        assert!(mapping.eq("\\__{^-_-^}", "|__[~-_-~]"));
+        assert!(mapping.eq("Catinator", "catinator"));
+        assert!(!mapping.eq("catinator", "catinator_"));
+    }
+
+    #[test]
+    fn test_strict_rfc1459() {
+        let mapping = CaseMapping::StrictRfc1459;
+        assert!(mapping.eq("[cat]", "{CAT}"));
+        assert!(!mapping.eq("~cat", "^cat"));
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mapping = CaseMapping::Ascii;
+        assert!(mapping.eq("CAT", "cat"));
+        assert!(!mapping.eq("[cat]", "{cat}"));
+        assert_eq!(mapping.lowercase("[CAT]"), "[cat]");
+    }
+}
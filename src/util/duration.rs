@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+/// Parse a human readable duration like `30s`, `5m` or `1h30m`.
+///
+/// Supported units are `ms`, `s`, `m`, `h` and `d`.
+///
+/// ```
+/// use std::time::Duration;
+/// use catinator::util::parse_duration;
+///
+/// assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+/// ```
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let mut total = Duration::ZERO;
+    let mut rest = input.trim();
+
+    if rest.is_empty() {
+        bail!("empty duration");
+    }
+
+    while !rest.is_empty() {
+        let split = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .with_context(|| format!("missing unit in duration {:?}", input))?;
+        let (number, tail) = rest.split_at(split);
+        let number: u64 = number
+            .parse()
+            .with_context(|| format!("expected a number in duration {:?}", input))?;
+
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+
+        let millis: u64 = match unit {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            _ => bail!("unknown unit {:?} in duration {:?}", unit, input),
+        };
+
+        total = number
+            .checked_mul(millis)
+            .map(Duration::from_millis)
+            .and_then(|duration| total.checked_add(duration))
+            .with_context(|| format!("duration {:?} is too long", input))?;
+
+        rest = tail;
+    }
+
+    Ok(total)
+}
+
+/// Format a duration in the format accepted by [parse_duration]
+pub fn format_duration(duration: Duration) -> String {
+    let mut millis = duration.as_millis();
+    let mut output = String::new();
+
+    for (unit, size) in [
+        ("d", 24 * 60 * 60 * 1000),
+        ("h", 60 * 60 * 1000),
+        ("m", 60 * 1000),
+        ("s", 1000),
+        ("ms", 1),
+    ] {
+        if millis >= size {
+            output.push_str(&format!("{}{}", millis / size, unit));
+            millis %= size;
+        }
+    }
+
+    if output.is_empty() {
+        output.push_str("0s");
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1d2h").unwrap(), Duration::from_secs(93600));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("5 minutes").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_overflow() {
+        let err = parse_duration("999999999999999d").unwrap_err();
+        assert_eq!(err.to_string(), "duration \"999999999999999d\" is too long");
+        assert!(parse_duration("18446744073709551615s").is_err());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1s500ms");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+}
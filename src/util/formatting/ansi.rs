@@ -0,0 +1,129 @@
+/// ANSI 256 color codes of the basic 16 IRC colors, see [Color](super::Color)
+const ANSI_COLORS: [u8; 16] = [15, 0, 4, 2, 9, 1, 5, 208, 11, 10, 6, 14, 12, 13, 8, 7];
+
+/// Parse the up to two digits of an IRC color code at the start of `chars`
+fn parse_color(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<u8> {
+    let mut color = None;
+
+    for _ in 0..2 {
+        match chars.peek().and_then(|c| c.to_digit(10)) {
+            Some(digit) => {
+                color = Some(color.unwrap_or(0) * 10 + digit as u8);
+                chars.next();
+            }
+            None => break,
+        }
+    }
+
+    color
+}
+
+/// ANSI sequence to set the IRC `color` in the foreground or background,
+/// extended and default colors reset to the terminal default
+fn ansi_color(color: u8, background: bool) -> String {
+    match (ANSI_COLORS.get(color as usize), background) {
+        (Some(color), false) => format!("\x1b[38;5;{}m", color),
+        (Some(color), true) => format!("\x1b[48;5;{}m", color),
+        (None, false) => "\x1b[39m".to_string(),
+        (None, true) => "\x1b[49m".to_string(),
+    }
+}
+
+/// Render IRC formatting codes in `text` as ANSI escape sequences for a terminal
+///
+/// ```
+/// use catinator::util::{to_ansi, Formatting};
+///
+/// assert_eq!(to_ansi(&"meow".bold()), "\x1b[1mmeow\x1b[22m\x1b[0m");
+/// assert_eq!(to_ansi("meow"), "meow");
+/// ```
+pub fn to_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut formatted = false;
+    // bold, italic, underline, strikethrough and reverse
+    let mut toggles = [false; 5];
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let toggle = match c {
+            '\x02' => Some((0, 1, 22)),
+            '\x1d' => Some((1, 3, 23)),
+            '\x1f' => Some((2, 4, 24)),
+            '\x1e' => Some((3, 9, 29)),
+            '\x16' => Some((4, 7, 27)),
+            _ => None,
+        };
+
+        if let Some((index, on, off)) = toggle {
+            toggles[index] = !toggles[index];
+            let code = if toggles[index] { on } else { off };
+            out.push_str(&format!("\x1b[{}m", code));
+            formatted = true;
+            continue;
+        }
+
+        match c {
+            '\x03' => {
+                formatted = true;
+                match parse_color(&mut chars) {
+                    Some(foreground) => {
+                        out.push_str(&ansi_color(foreground, false));
+
+                        let mut lookahead = chars.clone();
+                        if lookahead.next() == Some(',') {
+                            if let Some(background) = parse_color(&mut lookahead) {
+                                out.push_str(&ansi_color(background, true));
+                                chars = lookahead;
+                            }
+                        }
+                    }
+                    None => out.push_str("\x1b[39;49m"),
+                }
+            }
+            '\x0f' => {
+                toggles = [false; 5];
+                out.push_str("\x1b[0m");
+            }
+            // monospace has no equivalent in a terminal
+            '\x11' => {}
+            c => out.push(c),
+        }
+    }
+
+    if formatted {
+        out.push_str("\x1b[0m");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_ansi() {
+        assert_eq!(to_ansi("plain text"), "plain text");
+        assert_eq!(
+            to_ansi("\x02bold\x02 \x1ditalic"),
+            "\x1b[1mbold\x1b[22m \x1b[3mitalic\x1b[0m"
+        );
+        assert_eq!(to_ansi("\x11code\x11"), "code");
+        assert_eq!(to_ansi("\x1fa\x0fb"), "\x1b[4ma\x1b[0mb\x1b[0m");
+    }
+
+    #[test]
+    fn test_to_ansi_colors() {
+        assert_eq!(to_ansi("\x034red"), "\x1b[38;5;9mred\x1b[0m");
+        assert_eq!(
+            to_ansi("\x0304,01red"),
+            "\x1b[38;5;9m\x1b[48;5;0mred\x1b[0m"
+        );
+        // a comma without a background color is text
+        assert_eq!(to_ansi("\x034,meow"), "\x1b[38;5;9m,meow\x1b[0m");
+        // at most two digits are part of the color
+        assert_eq!(to_ansi("\x031234"), "\x1b[38;5;12m34\x1b[0m");
+        assert_eq!(to_ansi("\x034a\x03b"), "\x1b[38;5;9ma\x1b[39;49mb\x1b[0m");
+        assert_eq!(to_ansi("\x0399a"), "\x1b[39ma\x1b[0m");
+    }
+}
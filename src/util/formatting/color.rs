@@ -1,15 +1,15 @@
 /// The basic 16 IRC color codes
 pub enum Color {
-    White = 00,
-    Black = 01,
-    Blue = 02,
-    Green = 03,
-    Red = 04,
-    Brown = 05,
-    Magenta = 06,
-    Orange = 07,
-    Yellow = 08,
-    LightGreen = 09,
+    White = 0,
+    Black = 1,
+    Blue = 2,
+    Green = 3,
+    Red = 4,
+    Brown = 5,
+    Magenta = 6,
+    Orange = 7,
+    Yellow = 8,
+    LightGreen = 9,
     Cyan = 10,
     LightCyan = 11,
     LightBlue = 12,
@@ -1,10 +1,12 @@
 //! Tools for formatting irc messages
 
-mod truncate;
+mod ansi;
 mod color;
+mod truncate;
 
-pub use truncate::*;
+pub use ansi::*;
 pub use color::*;
+pub use truncate::*;
 
 /// Turn strings bold, italic,underline, strikethrough, and monospace.
 ///
@@ -47,35 +49,35 @@ impl<'r> Formatting<'r> for String {
         self.insert_str(0, Self::BOLD);
         self.push_str(Self::BOLD);
 
-        return self;
+        self
     }
 
     fn italic(mut self) -> Self {
         self.insert_str(0, Self::ITALIC);
         self.push_str(Self::ITALIC);
 
-        return self;
+        self
     }
 
     fn underline(mut self) -> Self {
         self.insert_str(0, Self::UNDERLINE);
         self.push_str(Self::UNDERLINE);
 
-        return self;
+        self
     }
 
     fn strikethrough(mut self) -> Self {
         self.insert_str(0, Self::STRIKETHROUGH);
         self.push_str(Self::STRIKETHROUGH);
 
-        return self;
+        self
     }
 
     fn monospace(mut self) -> Self {
         self.insert_str(0, Self::MONOSPACE);
         self.push_str(Self::MONOSPACE);
 
-        return self;
+        self
     }
 
     // TODO implement color codes
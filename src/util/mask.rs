@@ -0,0 +1,67 @@
+/// Match a hostmask pattern against an input like `nick!user@host`.
+///
+/// The pattern supports the usual IRC wildcards, `*` matches any number
+/// of characters and `?` matches exactly one. Matching is case-insensitive.
+///
+/// ```
+/// use catinator::util::mask_matches;
+///
+/// assert!(mask_matches("*!*@cocaine.farm", "audron!audron@cocaine.farm"));
+/// assert!(!mask_matches("*!*@cocaine.farm", "basso!basso@example.com"));
+/// ```
+pub fn mask_matches(pattern: &str, input: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let input: Vec<char> = input.chars().flat_map(char::to_lowercase).collect();
+
+    let (mut p, mut i) = (0, 0);
+    // Position of the last `*` in the pattern and the input position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < input.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == input[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star, tried)) => {
+                    p = star + 1;
+                    i = tried + 1;
+                    backtrack = Some((star, tried + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mask_matches;
+
+    #[test]
+    fn test_mask_exact() {
+        assert!(mask_matches("nick!user@host", "nick!user@host"));
+        assert!(!mask_matches("nick!user@host", "nick!user@host2"));
+    }
+
+    #[test]
+    fn test_mask_wildcards() {
+        assert!(mask_matches("*!*@*.farm", "audron!audron@cocaine.farm"));
+        assert!(mask_matches("a?dron!*@*", "audron!~a@host"));
+        assert!(mask_matches("*", ""));
+        assert!(!mask_matches("a?dron!*@*", "adron!~a@host"));
+        assert!(!mask_matches("*!*@*.farm", "audron!audron@cocaine.farms"));
+    }
+
+    #[test]
+    fn test_mask_case_insensitive() {
+        assert!(mask_matches("AUDRON!*@*", "audron!audron@cocaine.farm"));
+    }
+}
@@ -0,0 +1,56 @@
+use irc::client::prelude::*;
+
+/// Get the channel a message was sent to or is about.
+///
+/// Returns `None` for private messages and commands that are not related to a channel.
+pub fn channel(msg: &Message) -> Option<&str> {
+    let channel = match &msg.command {
+        Command::PRIVMSG(target, _) | Command::NOTICE(target, _) => target,
+        Command::JOIN(channel, ..)
+        | Command::PART(channel, ..)
+        | Command::KICK(channel, ..)
+        | Command::TOPIC(channel, ..)
+        | Command::ChannelMODE(channel, ..) => channel,
+        _ => return None,
+    };
+
+    if channel.is_channel_name() {
+        Some(channel)
+    } else {
+        None
+    }
+}
+
+/// Get the full `nick!user@host` hostmask of the messages sender.
+pub fn hostmask(msg: &Message) -> Option<String> {
+    match &msg.prefix {
+        Some(Prefix::Nickname(nick, user, host)) => Some(format!("{}!{}@{}", nick, user, host)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel() {
+        let msg = Message::new(Some("nick!user@host"), "PRIVMSG", vec!["#chan", "hi"]).unwrap();
+        assert_eq!(channel(&msg), Some("#chan"));
+
+        let msg = Message::new(Some("nick!user@host"), "PRIVMSG", vec!["bot", "hi"]).unwrap();
+        assert_eq!(channel(&msg), None);
+
+        let msg = Message::new(Some("nick!user@host"), "JOIN", vec!["#chan"]).unwrap();
+        assert_eq!(channel(&msg), Some("#chan"));
+    }
+
+    #[test]
+    fn test_hostmask() {
+        let msg = Message::new(Some("nick!user@host"), "PRIVMSG", vec!["#chan", "hi"]).unwrap();
+        assert_eq!(hostmask(&msg).as_deref(), Some("nick!user@host"));
+
+        let msg = Message::new(Some("irc.server.net"), "PING", vec!["irc.server.net"]).unwrap();
+        assert_eq!(hostmask(&msg), None);
+    }
+}
@@ -1,7 +1,15 @@
 //! Utilities for dealing with IRC and bot making
 
+mod casemapping;
+mod duration;
 mod formatting;
+mod mask;
+mod message;
 mod web;
 
+pub use casemapping::*;
+pub use duration::*;
 pub use formatting::*;
+pub use mask::*;
+pub use message::*;
 pub use web::*;
@@ -0,0 +1,198 @@
+//! Tests running the event loop of the catinator macro over messages fed to a [TestBot].
+//!
+//! Every test feeds its messages to [run], which ends once they are all handled,
+//! and looks at what the bot sent in reply.
+
+use anyhow::Result;
+
+use catinator::testing::{privmsg, Command, Message, TestBot};
+use catinator::{assert_acted, assert_noticed, assert_replied, Bot, Context};
+
+fn high(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("high")
+}
+
+fn low(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("low")
+}
+
+fn eat(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("eat")
+}
+
+fn hungry(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("hungry")
+}
+
+fn with_prefix(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("prefixed")
+}
+
+fn without_prefix(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("unprefixed")
+}
+
+/// Reply with what the context knows about the invocation
+fn whoami(_bot: &Bot, ctx: Context) -> Result<()> {
+    let nick = ctx.nick.unwrap_or("nobody");
+    ctx.reply(&format!(
+        "{} in {:?}: {}",
+        nick,
+        ctx.channel,
+        ctx.args.join("|")
+    ))?;
+    ctx.reply_notice(&format!("private: {}", ctx.private))?;
+    ctx.reply_action(&format!("looks at {}", nick))
+}
+
+fn add(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply(&format!("added {}", ctx.input))
+}
+
+fn rand(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply("meow")
+}
+
+fn welcome(bot: &Bot, _msg: Message) -> Result<()> {
+    Ok(bot.send_privmsg("#cats", "welcome")?)
+}
+
+fn presence(bot: &Bot, msg: Message) -> Result<()> {
+    let what = match msg.command {
+        Command::JOIN(..) => "join",
+        Command::PART(..) => "part",
+        _ => "other",
+    };
+    Ok(bot.send_privmsg("#cats", what)?)
+}
+
+/// Count the cats of every match, like "2 cats and 3 cats" -> "2 matches: 2,3"
+fn count(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply(&format!(
+        "{} matches: {}",
+        ctx.captures.len(),
+        ctx.capture_all(1).join(",")
+    ))
+}
+
+/// Run the event loop over `messages`
+async fn run(messages: Vec<Message>) -> TestBot {
+    let mut bot = TestBot::new().await;
+    bot.feed(messages);
+
+    catinator::catinator![
+        hook("welcome", "Greet", RPL_WELCOME, welcome),
+        hook("presence", "Track", [JOIN, PART], presence),
+        command("whoami", "Describe the caller", whoami),
+        group("quote", "Manage quotes", [
+            command("add", "Add a quote", add)
+            command("rand", "Show a random quote", rand)
+        ]),
+        matcher("count", "Count", r"(\d+) cats", count),
+        matcher("low", "Low", r"purr", low),
+        matcher("high", "High", r"purr", high, priority = 10),
+        matcher("hungry", "Hungry", r"nom", hungry),
+        matcher("eat", "Eat", r"nom", eat, priority = 5, consume = true),
+        matcher("unprefixed", "Hiss", r"hiss", without_prefix),
+        matcher("prefixed", "Hiss", r"hiss", with_prefix, prefixed = true),
+    ];
+
+    bot
+}
+
+/// The texts of all PRIVMSGs the bot sent, in order
+fn replies(bot: &TestBot) -> Vec<String> {
+    bot.take_sent()
+        .into_iter()
+        .filter_map(|msg| match msg.command {
+            Command::PRIVMSG(_, text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_matcher_priority() {
+    let bot = run(vec![privmsg("audron", "#cats", "purr purr")]).await;
+    assert_eq!(replies(&bot), ["high", "low"]);
+}
+
+#[tokio::test]
+async fn test_matcher_consume() {
+    let bot = run(vec![privmsg("audron", "#cats", "nom")]).await;
+    assert_eq!(replies(&bot), ["eat"]);
+}
+
+#[tokio::test]
+async fn test_matcher_prefixed() {
+    let bot = run(vec![privmsg("audron", "#cats", ":hiss")]).await;
+    assert_eq!(replies(&bot), ["prefixed"]);
+
+    let bot = run(vec![privmsg("audron", "#cats", "hiss")]).await;
+    assert_eq!(replies(&bot), ["unprefixed", "prefixed"]);
+}
+
+#[tokio::test]
+async fn test_context() {
+    let bot = run(vec![privmsg("audron", "#cats", ":whoami  a b ")]).await;
+    assert_replied!(bot, "#cats", "audron in Some(\"#cats\"): a|b");
+    assert_noticed!(bot, "audron", "private: false");
+    assert_acted!(bot, "#cats", "looks at audron");
+
+    // replies to private queries go to the sender
+    let bot = run(vec![privmsg("audron", "catinator", "whoami")]).await;
+    assert_replied!(bot, "audron", "audron in None: ");
+    assert_noticed!(bot, "audron", "private: true");
+    assert_acted!(bot, "audron", "looks at audron");
+}
+
+#[tokio::test]
+async fn test_group() {
+    let bot = run(vec![privmsg("audron", "#cats", ":quote add <audron> meow")]).await;
+    assert_eq!(replies(&bot), ["added <audron> meow"]);
+
+    let bot = run(vec![privmsg("audron", "#cats", ":quote rand")]).await;
+    assert_eq!(replies(&bot), ["meow"]);
+}
+
+#[tokio::test]
+async fn test_group_usage() {
+    for text in [":quote", ":quote purr"] {
+        let bot = run(vec![privmsg("audron", "#cats", text)]).await;
+        assert_eq!(
+            bot.take_sent(),
+            [
+                "usage: quote <add|rand>",
+                "  quote add: Add a quote",
+                "  quote rand: Show a random quote",
+            ]
+            .map(|text| Message::from(Command::NOTICE("audron".to_string(), text.to_string())))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_hooks() {
+    let messages = [
+        ":ircd 001 catinator :Welcome",
+        ":ircd 376 catinator :End of MOTD",
+        ":audron!audron@example.com JOIN #cats",
+        ":audron!audron@example.com PART #cats",
+        ":audron!audron@example.com QUIT :bye",
+    ]
+    .map(|line| line.parse().unwrap());
+
+    let bot = run(messages.to_vec()).await;
+    assert_eq!(replies(&bot), ["welcome", "join", "part"]);
+}
+
+#[tokio::test]
+async fn test_captures() {
+    let bot = run(vec![privmsg(
+        "audron",
+        "#cats",
+        "2 cats and 3 cats and 4 dogs",
+    )])
+    .await;
+    assert_eq!(replies(&bot), ["2 matches: 2,3"]);
+}
@@ -0,0 +1,457 @@
+//! End to end tests running the bot against a fake irc server on localhost.
+//!
+//! Every test binds its own [Ircd] and scripts the server side of the conversation
+//! in a spawned task, while the bot and its event loop run in the test itself.
+//! The event loop ends when the server closes the connection.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use irc::client::prelude::*;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use catinator::{Bot, Context};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A fake irc server accepting connections on a random port
+struct Ircd {
+    listener: TcpListener,
+}
+
+impl Ircd {
+    async fn bind() -> Ircd {
+        Ircd {
+            listener: TcpListener::bind("127.0.0.1:0").await.unwrap(),
+        }
+    }
+
+    /// Config for a bot connecting to this server, `extra` is merged into it
+    fn figment(&self, extra: &str) -> Figment {
+        let port = self.listener.local_addr().unwrap().port();
+        let config = format!(
+            r##"
+            [user]
+            nickname = "catinator"
+            username = "catinator"
+            realname = "moaw"
+
+            [server]
+            hostname = "127.0.0.1"
+            port = {}
+            tls = false
+            channels = ["#cats"]
+
+            [settings]
+            prefix = "!"
+            "##,
+            port
+        );
+
+        Figment::from(Toml::string(&config)).merge(Toml::string(extra))
+    }
+
+    /// Accept a connection and run the server side `script` on it
+    fn serve<F, Fut>(self, script: F) -> JoinHandle<()>
+    where
+        F: FnOnce(Conn) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        tokio::spawn(async move {
+            let (stream, _) = tokio::time::timeout(TIMEOUT, self.listener.accept())
+                .await
+                .expect("the bot did not connect")
+                .unwrap();
+            script(Conn::new(stream)).await
+        })
+    }
+}
+
+/// The server side of a connection
+struct Conn {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    /// Everything received so far, for error messages
+    received: Vec<String>,
+}
+
+impl Conn {
+    fn new(stream: TcpStream) -> Conn {
+        let (reader, writer) = stream.into_split();
+        Conn {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            received: Vec::new(),
+        }
+    }
+
+    async fn send(&mut self, line: &str) {
+        self.writer
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Read messages until the raw line matches `pred`, panicking on timeout or disconnect
+    async fn expect(&mut self, what: &str, pred: impl Fn(&str) -> bool) -> Message {
+        loop {
+            let line = tokio::time::timeout(TIMEOUT, self.lines.next_line())
+                .await
+                .unwrap_or_else(|_| panic!("timed out waiting for {}: {:#?}", what, self.received))
+                .unwrap()
+                .unwrap_or_else(|| {
+                    panic!("disconnected waiting for {}: {:#?}", what, self.received)
+                });
+
+            self.received.push(line.clone());
+            if pred(&line) {
+                return line.parse().unwrap();
+            }
+        }
+    }
+
+    /// Read messages until the raw line starts with `prefix`
+    async fn expect_line(&mut self, prefix: &str) -> Message {
+        self.expect(prefix, |line| line.starts_with(prefix)).await
+    }
+
+    /// Read messages until one has the `command`
+    async fn expect_command(&mut self, command: Command) -> Message {
+        let what = format!("{:?}", command);
+        self.expect(&what, |line| {
+            line.parse::<Message>()
+                .is_ok_and(|msg| msg.command == command)
+        })
+        .await
+    }
+
+    /// Complete the registration of the bot
+    async fn welcome(&mut self) {
+        self.expect_line("USER catinator 0 * moaw").await;
+        self.send(":ircd 001 catinator :Welcome").await;
+        self.send(":ircd 376 catinator :End of MOTD").await;
+    }
+}
+
+/// Echo the input back, for testing dispatch
+fn echo(_bot: &Bot, ctx: Context) -> Result<()> {
+    ctx.reply(ctx.input)
+}
+
+/// Run the bot with the `echo` command and the `sasl` hook until the server disconnects
+async fn run(mut bot: Bot) {
+    catinator::catinator![
+        hook(
+            "sasl",
+            "Handle Authentication.",
+            AUTHENTICATE,
+            catinator::hooks::sasl
+        ),
+        command("echo", "Echo the input back", echo),
+    ];
+}
+
+async fn join(server: JoinHandle<()>) {
+    tokio::time::timeout(TIMEOUT, server)
+        .await
+        .expect("the server script did not finish")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_registration() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.expect_line("CAP REQ account-tag").await;
+        conn.expect_line("CAP REQ multi-prefix").await;
+        conn.expect_line("NICK catinator").await;
+        conn.expect_line("USER catinator 0 * moaw").await;
+        conn.expect_line("CAP END").await;
+
+        // no server password is configured
+        assert!(!conn.received.iter().any(|line| line.starts_with("PASS")));
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_password() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("[server]\npassword = 'hunter2'");
+
+    let server = ircd.serve(|mut conn| async move {
+        let first = conn.expect("any message", |_| true).await;
+        assert_eq!(first.command, Command::PASS("hunter2".to_string()));
+
+        conn.expect_line("NICK catinator").await;
+        conn.expect_line("CAP END").await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_sasl() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("[user]\npassword = 'hunter22'\n[server]\nsasl = true");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.expect_line("CAP REQ sasl").await;
+        conn.expect_line("NICK catinator").await;
+        conn.expect_line("AUTHENTICATE PLAIN").await;
+
+        conn.send("AUTHENTICATE +").await;
+        let msg = conn.expect_line("AUTHENTICATE").await;
+        // base64 of "\0catinator\0hunter22" with padding
+        assert_eq!(
+            msg.command,
+            Command::AUTHENTICATE("AGNhdGluYXRvcgBodW50ZXIyMg==".to_string())
+        );
+        conn.expect_line("CAP END").await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_join() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.welcome().await;
+        conn.expect_line("JOIN #cats").await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_command() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.welcome().await;
+        conn.expect_line("JOIN #cats").await;
+
+        conn.send(":audron!audron@example.com PRIVMSG #cats :!echo meow meow")
+            .await;
+        conn.expect_command(Command::PRIVMSG(
+            "#cats".to_string(),
+            "meow meow".to_string(),
+        ))
+        .await;
+
+        // addressing the bot by nick works as well
+        conn.send(":audron!audron@example.com PRIVMSG #cats :catinator: echo purr")
+            .await;
+        conn.expect_command(Command::PRIVMSG("#cats".to_string(), "purr".to_string()))
+            .await;
+
+        // the wrong prefix is ignored, the next reply is for the message after it
+        conn.send(":audron!audron@example.com PRIVMSG #cats ::echo nope")
+            .await;
+        conn.send(":audron!audron@example.com PRIVMSG #cats :!echo yes")
+            .await;
+        let msg = conn.expect_line("PRIVMSG").await;
+        assert_eq!(
+            msg.command,
+            Command::PRIVMSG("#cats".to_string(), "yes".to_string())
+        );
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_help() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.welcome().await;
+
+        conn.send(":audron!audron@example.com PRIVMSG #cats :!help")
+            .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "COMMANDS:".to_string(),
+        ))
+        .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "  echo: Echo the input back".to_string(),
+        ))
+        .await;
+        // builtins are listed with the commands
+        conn.expect_line("NOTICE audron :  hooks: ").await;
+        conn.expect_command(Command::NOTICE("audron".to_string(), "HOOKS:".to_string()))
+            .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "  sasl: Handle Authentication.".to_string(),
+        ))
+        .await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+/// Managing another channel than the one the command is sent in checks the
+/// users modes in that channel
+#[tokio::test]
+async fn test_target_channel_permission() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.welcome().await;
+
+        for (channel, names) in [("#a", "@audron"), ("#b", "audron")] {
+            conn.send(&format!(
+                ":catinator!catinator@example.com JOIN {}",
+                channel
+            ))
+            .await;
+            conn.send(&format!(
+                ":ircd 353 catinator = {} :catinator {}",
+                channel, names
+            ))
+            .await;
+            conn.send(&format!(
+                ":ircd 366 catinator {} :End of /NAMES list",
+                channel
+            ))
+            .await;
+        }
+
+        conn.send(":audron!audron@example.com PRIVMSG #a :!disable echo #b")
+            .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "disable requires op permissions in #b".to_string(),
+        ))
+        .await;
+
+        conn.send(":audron!audron@example.com PRIVMSG #a :!hooks #b")
+            .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "hooks requires op permissions in #b".to_string(),
+        ))
+        .await;
+
+        conn.send(":audron!audron@example.com PRIVMSG #a :!disable echo #a")
+            .await;
+        conn.expect_command(Command::NOTICE(
+            "audron".to_string(),
+            "echo is now disabled in #a".to_string(),
+        ))
+        .await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+}
+
+/// The bot does not reconnect by itself, the event loop ends when the connection is
+/// closed so the process can be restarted. A new bot joins and answers commands
+/// like the first one.
+#[tokio::test]
+async fn test_restart_after_disconnect() {
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment("");
+    let listener = ircd.listener;
+
+    let server = tokio::spawn(async move {
+        for round in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut conn = Conn::new(stream);
+            conn.welcome().await;
+            conn.expect_line("JOIN #cats").await;
+
+            conn.send(&format!(
+                ":audron!audron@example.com PRIVMSG #cats :!echo round {}",
+                round
+            ))
+            .await;
+            conn.expect_command(Command::PRIVMSG(
+                "#cats".to_string(),
+                format!("round {}", round),
+            ))
+            .await;
+
+            conn.send("ERROR :Closing Link").await;
+        }
+    });
+
+    for _ in 0..2 {
+        let bot = Bot::with_figment(figment.clone()).await.unwrap();
+        tokio::time::timeout(TIMEOUT, run(bot))
+            .await
+            .expect("the event loop did not end after the disconnect");
+    }
+
+    join(server).await;
+}
+
+#[tokio::test]
+async fn test_record() {
+    let path = std::env::temp_dir().join(format!("catinator-e2e-{}.jsonl", std::process::id()));
+
+    let ircd = Ircd::bind().await;
+    let figment = ircd.figment(&format!(
+        "[server]\npassword = 'hunter2'\n[settings]\nrecord = {:?}",
+        path
+    ));
+
+    let server = ircd.serve(|mut conn| async move {
+        conn.welcome().await;
+        conn.send(":audron!audron@example.com PRIVMSG #cats :!echo meow")
+            .await;
+        conn.expect_command(Command::PRIVMSG("#cats".to_string(), "meow".to_string()))
+            .await;
+    });
+
+    let bot = Bot::with_figment(figment).await.unwrap();
+    tokio::time::timeout(TIMEOUT, run(bot)).await.unwrap();
+    join(server).await;
+
+    let entries = catinator::record::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<(catinator::record::Direction, &str)> = entries
+        .iter()
+        .map(|entry| (entry.direction, entry.line.as_str()))
+        .collect();
+
+    use catinator::record::Direction::{In, Out};
+    assert_eq!(lines[0], (Out, "PASS <redacted>"));
+    assert!(lines.contains(&(In, ":audron!audron@example.com PRIVMSG #cats :!echo meow")));
+    assert!(lines.contains(&(Out, "PRIVMSG #cats meow")));
+    assert!(!entries.iter().any(|entry| entry.line.contains("hunter2")));
+}